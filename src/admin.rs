@@ -0,0 +1,257 @@
+use std::{fmt::Write as _, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+};
+use log::error;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    MessageResponse, Webhooks,
+    cache::{CacheStats, MetadataCache},
+    health::{HealthStatus, UpstreamHealth},
+    notify::{NotificationQueue, QueuedNotification},
+};
+
+pub const TAG: &str = "admin";
+
+pub struct AdminApi {
+    queue: Arc<NotificationQueue>,
+    health: Arc<UpstreamHealth>,
+    webhooks: Arc<Webhooks>,
+    cache: Arc<MetadataCache>,
+}
+
+fn internal_error(e: anyhow::Error) -> (StatusCode, Json<MessageResponse>) {
+    error!("{e}");
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(MessageResponse::new(e.to_string())),
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/status",
+    responses(
+        (status = StatusCode::OK, description = "Health of Seerr, the *arr instances and the media server", body = HealthStatus)
+    ),
+    tag  = TAG
+)]
+async fn get_status(State(api): State<Arc<AdminApi>>) -> impl IntoResponse {
+    Json(api.health.status())
+}
+
+#[utoipa::path(
+    get,
+    path = "/cache",
+    responses(
+        (status = StatusCode::OK, description = "Entries, hits and misses of the caches of Seerr users, media details and posters", body = Vec<CacheStats>)
+    ),
+    tag  = TAG
+)]
+async fn get_cache(State(api): State<Arc<AdminApi>>) -> impl IntoResponse {
+    Json(api.cache.stats())
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    responses(
+        (status = StatusCode::OK, description = "Metrics in the Prometheus text format", body = String, content_type = "text/plain")
+    ),
+    tag  = TAG
+)]
+async fn get_metrics(State(api): State<Arc<AdminApi>>) -> impl IntoResponse {
+    let stats = api.webhooks.queue_stats();
+    let mut metrics = String::new();
+    write_metric(
+        &mut metrics,
+        ("informarr_webhook_queue_depth", "gauge"),
+        "Webhook events waiting to be processed",
+        "webhook",
+        stats
+            .iter()
+            .map(|(webhook, stats)| (*webhook, stats.depth as u64)),
+    );
+    write_metric(
+        &mut metrics,
+        ("informarr_webhook_queue_capacity", "gauge"),
+        "Webhook events that can wait before the webhook answers 503",
+        "webhook",
+        stats
+            .iter()
+            .map(|(webhook, stats)| (*webhook, stats.capacity as u64)),
+    );
+    write_metric(
+        &mut metrics,
+        ("informarr_webhook_events_rejected_total", "counter"),
+        "Webhook events answered with a 503 because the queue was full",
+        "webhook",
+        stats
+            .iter()
+            .map(|(webhook, stats)| (*webhook, stats.rejected)),
+    );
+
+    let stats = api.cache.stats();
+    write_metric(
+        &mut metrics,
+        ("informarr_cache_entries", "gauge"),
+        "Entries stored in the cache",
+        "cache",
+        stats
+            .iter()
+            .map(|stats| (stats.name.as_str(), stats.entries as u64)),
+    );
+    write_metric(
+        &mut metrics,
+        ("informarr_cache_hits_total", "counter"),
+        "Lookups answered by the cache",
+        "cache",
+        stats.iter().map(|stats| (stats.name.as_str(), stats.hits)),
+    );
+    write_metric(
+        &mut metrics,
+        ("informarr_cache_misses_total", "counter"),
+        "Lookups fetched from the upstream because the entry was missing or expired",
+        "cache",
+        stats
+            .iter()
+            .map(|stats| (stats.name.as_str(), stats.misses)),
+    );
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics,
+    )
+}
+
+/// Writes a metric in the Prometheus text format with a value by label value
+fn write_metric<'a>(
+    metrics: &mut String,
+    (name, kind): (&str, &str),
+    help: &str,
+    label: &str,
+    values: impl Iterator<Item = (&'a str, u64)>,
+) {
+    let _ = writeln!(metrics, "# HELP {name} {help}");
+    let _ = writeln!(metrics, "# TYPE {name} {kind}");
+    for (label_value, value) in values {
+        let _ = writeln!(metrics, "{name}{{{label}=\"{label_value}\"}} {value}");
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/queue",
+    responses(
+        (status = StatusCode::OK, description = "Notifications waiting to be sent", body = Vec<QueuedNotification>),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = MessageResponse)
+    ),
+    tag  = TAG
+)]
+async fn get_queue(State(api): State<Arc<AdminApi>>) -> impl IntoResponse {
+    api.queue.pending().await.map(Json).map_err(internal_error)
+}
+
+#[utoipa::path(
+    get,
+    path = "/dead-letters",
+    responses(
+        (status = StatusCode::OK, description = "Notifications that could not be sent", body = Vec<QueuedNotification>),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = MessageResponse)
+    ),
+    tag  = TAG
+)]
+async fn get_dead_letters(State(api): State<Arc<AdminApi>>) -> impl IntoResponse {
+    api.queue
+        .dead_letters()
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+#[utoipa::path(
+    post,
+    path = "/dead-letters/redrive",
+    responses(
+        (status = StatusCode::OK, description = "Dead letters queued again", body = MessageResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = MessageResponse)
+    ),
+    tag  = TAG
+)]
+async fn redrive_dead_letters(State(api): State<Arc<AdminApi>>) -> impl IntoResponse {
+    api.queue
+        .redrive_all()
+        .await
+        .map(|count| {
+            Json(MessageResponse::new(format!(
+                "{count} notifications queued again"
+            )))
+        })
+        .map_err(internal_error)
+}
+
+#[utoipa::path(
+    post,
+    path = "/dead-letters/{id}/redrive",
+    params(("id" = String, Path, description = "Id of the dead-lettered notification")),
+    responses(
+        (status = StatusCode::OK, description = "Dead letter queued again", body = MessageResponse),
+        (status = StatusCode::NOT_FOUND, description = "Dead letter not found", body = MessageResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = MessageResponse)
+    ),
+    tag  = TAG
+)]
+async fn redrive_dead_letter(
+    State(api): State<Arc<AdminApi>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match api.queue.redrive(&id).await {
+        Ok(true) => (StatusCode::OK, Json(MessageResponse::ok())),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(MessageResponse::new(format!("Dead letter {id} not found"))),
+        ),
+        Err(e) => internal_error(e),
+    }
+}
+
+impl AdminApi {
+    pub fn new(
+        queue: Arc<NotificationQueue>,
+        health: Arc<UpstreamHealth>,
+        webhooks: Arc<Webhooks>,
+        cache: Arc<MetadataCache>,
+    ) -> Arc<Self> {
+        Arc::new(AdminApi {
+            queue,
+            health,
+            webhooks,
+            cache,
+        })
+    }
+
+    pub fn router(self: Arc<Self>) -> OpenApiRouter {
+        Self::routes().with_state(self)
+    }
+
+    /// Specification of the endpoints, available without opening the queue
+    pub fn openapi() -> utoipa::openapi::OpenApi {
+        Self::routes().split_for_parts().1
+    }
+
+    fn routes() -> OpenApiRouter<Arc<Self>> {
+        OpenApiRouter::new()
+            .routes(routes!(get_status))
+            .routes(routes!(get_cache))
+            .routes(routes!(get_metrics))
+            .routes(routes!(get_queue))
+            .routes(routes!(get_dead_letters))
+            .routes(routes!(redrive_dead_letters))
+            .routes(routes!(redrive_dead_letter))
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use seerr::models::{SeerrMovieDetails, SeerrTvDetails, SeerrUser};
+use serde::Serialize;
+use tokio::time::Instant;
+use utoipa::ToSchema;
+
+use crate::config::SeerrConfig;
+
+/// Hits and misses of a cache since startup
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct CacheStats {
+    pub name: String,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Values fetched from an upstream, kept until they are older than the TTL or invalidated
+///
+/// The lock is never held while the values are fetched, two tasks missing the same key both
+/// fetch it and the last one is kept. An expired entry is dropped when it is read, the ones no
+/// longer read are swept by the inserts once per TTL.
+pub struct TtlCache<K, V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+    swept_at: Mutex<Instant>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            swept_at: Mutex::new(Instant::now()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+        let value = match entries.get(key) {
+            Some((inserted_at, value)) if inserted_at.elapsed() < self.ttl => Some(value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        };
+        let counter = if value.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    /// Stores the value, the expired entries are dropped if they were not swept for a TTL
+    pub fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.lock().unwrap();
+        let mut swept_at = self.swept_at.lock().unwrap();
+        if swept_at.elapsed() >= self.ttl {
+            entries.retain(|_, (inserted_at, _)| inserted_at.elapsed() < self.ttl);
+            *swept_at = Instant::now();
+        }
+        entries.insert(key, (Instant::now(), value));
+    }
+
+    /// Value of the key, fetched and stored if it is not cached
+    pub async fn get_or_fetch<E>(
+        &self,
+        key: K,
+        fetch: impl Future<Output = Result<V, E>>,
+    ) -> Result<V, E> {
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
+        }
+        let value = fetch.await?;
+        self.insert(key, value.clone());
+        Ok(value)
+    }
+
+    /// Drops the entries whose key matches
+    pub fn invalidate(&self, matches: impl Fn(&K) -> bool) {
+        self.entries.lock().unwrap().retain(|key, _| !matches(key));
+    }
+
+    pub fn stats(&self, name: &str) -> CacheStats {
+        CacheStats {
+            name: name.to_string(),
+            entries: self.entries.lock().unwrap().len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Details of a movie or show in the language they were requested in
+pub type MediaKey = (i32, Option<String>);
+
+/// Responses of Seerr and the posters fetched to notify, shared by the request handlers and the
+/// admin API
+///
+/// The details of a media item are invalidated by the Seerr webhooks about it, the users are
+/// only refreshed by the TTL since Seerr does not notify changes of their settings.
+pub struct MetadataCache {
+    pub users: TtlCache<i32, SeerrUser>,
+    pub movies: TtlCache<MediaKey, SeerrMovieDetails>,
+    pub shows: TtlCache<MediaKey, SeerrTvDetails>,
+    /// Dominant color of the posters by URL
+    pub poster_colors: TtlCache<String, u64>,
+}
+
+impl MetadataCache {
+    pub fn new(config: &SeerrConfig) -> Self {
+        let ttl = Duration::from_secs(config.cache_ttl * 60);
+        Self {
+            users: TtlCache::new(ttl),
+            movies: TtlCache::new(ttl),
+            shows: TtlCache::new(ttl),
+            poster_colors: TtlCache::new(ttl),
+        }
+    }
+
+    /// Drops the details of the movie or show, whose availability changed
+    pub fn invalidate_media(&self, media_type: &str, tmdb_id: i32) {
+        let matches = |(id, _): &MediaKey| *id == tmdb_id;
+        match media_type {
+            "movie" => self.movies.invalidate(matches),
+            "tv" => self.shows.invalidate(matches),
+            _ => {}
+        }
+    }
+
+    pub fn stats(&self) -> Vec<CacheStats> {
+        vec![
+            self.users.stats("users"),
+            self.movies.stats("movies"),
+            self.shows.stats("shows"),
+            self.poster_colors.stats("poster_colors"),
+        ]
+    }
+}
@@ -0,0 +1,186 @@
+use std::{net::IpAddr, path::PathBuf, sync::Arc};
+
+use anyhow::{Result, anyhow};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use tokio::fs;
+
+use crate::{
+    config::AppConfig,
+    controller::{self, WebhookEvent},
+    notify::{self, NotificationQueue},
+    reload::LoadedConfig,
+    templates::MessageTemplates,
+    webhooks::InstanceEvent,
+};
+
+const DEFAULT_PORT: u16 = 7532;
+
+const DEFAULT_ADMIN_PORT: u16 = 7533;
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Notifies Discord and Telegram when the requested media is available"
+)]
+pub struct Cli {
+    /// Configuration file, `config.yml` or `config.yaml` of the working directory by default
+    #[arg(short, long, global = true, env = "INFORMARR_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Runs the webhook server, the default command
+    Serve(ServeArgs),
+    /// Validates the configuration and exits
+    CheckConfig,
+    /// Lists the Seerr requests that are waiting to be available
+    ListRequests,
+    /// Sends a test notification to the configured destinations
+    SendTest,
+    /// Processes a webhook payload saved to a file as if the server received it
+    Replay(ReplayArgs),
+    /// Prints the OpenAPI specification of the server
+    Openapi,
+    /// Prints the JSON Schema of the configuration file, for editor autocompletion
+    ConfigSchema,
+    /// Queues the dead-lettered notifications again, all of them if no id is given
+    Redrive {
+        /// Id of the dead letter
+        id: Option<String>,
+    },
+}
+
+/// Also parsed on its own to run the server when no command is given
+#[derive(Parser, Debug, Clone)]
+pub struct ServeArgs {
+    /// Port the server listens on
+    #[arg(short, long, env = "INFORMARR_PORT", default_value_t = DEFAULT_PORT)]
+    pub port: u16,
+
+    /// Address the server binds to
+    #[arg(short, long, env = "INFORMARR_BIND", default_value = "0.0.0.0")]
+    pub bind: IpAddr,
+
+    /// Port of the admin API, served apart from the webhooks since it has no authentication
+    #[arg(long, env = "INFORMARR_ADMIN_PORT", default_value_t = DEFAULT_ADMIN_PORT)]
+    pub admin_port: u16,
+
+    /// Address the admin API binds to, only reachable from the host by default
+    #[arg(long, env = "INFORMARR_ADMIN_BIND", default_value = "127.0.0.1")]
+    pub admin_bind: IpAddr,
+}
+
+#[derive(Args, Debug)]
+pub struct ReplayArgs {
+    /// Service that sent the payload
+    #[arg(short, long, value_enum)]
+    pub source: ReplaySource,
+
+    /// Sonarr instance name, as given in the webhook path
+    #[arg(short, long)]
+    pub instance: Option<String>,
+
+    /// JSON payload of the webhook
+    pub payload: PathBuf,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum ReplaySource {
+    Sonarr,
+    Radarr,
+    Lidarr,
+    Readarr,
+    Seerr,
+}
+
+async fn load_config(cli: &Cli) -> Result<(AppConfig, MessageTemplates)> {
+    let config = LoadedConfig::load(cli.config.as_deref()).await?;
+    Ok((config.app_config, config.templates))
+}
+
+pub async fn open_queue(app_config: &AppConfig) -> Result<Arc<NotificationQueue>> {
+    Ok(Arc::new(
+        NotificationQueue::new(app_config.queue.clone())
+            .await
+            .map_err(|e| anyhow!("Could not open notification queue: {e}"))?,
+    ))
+}
+
+pub async fn check_config(cli: &Cli) -> Result<()> {
+    load_config(cli).await?;
+    println!("Configuration is valid");
+    Ok(())
+}
+
+pub fn print_config_schema() -> Result<()> {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&AppConfig::json_schema())?
+    );
+    Ok(())
+}
+
+pub async fn list_requests(cli: &Cli) -> Result<()> {
+    let (app_config, templates) = load_config(cli).await?;
+    let queue = open_queue(&app_config).await?;
+    for request in controller::pending_requests(app_config, templates, queue).await? {
+        println!("{request}");
+    }
+    Ok(())
+}
+
+pub async fn send_test(cli: &Cli) -> Result<()> {
+    let (app_config, templates) = load_config(cli).await?;
+    let queue = open_queue(&app_config).await?;
+    queue.track_pushed().await;
+    let (discord, telegram) = (app_config.discord.clone(), app_config.telegram.clone());
+    controller::send_test(app_config, templates, queue.clone()).await?;
+    notify::flush(queue, discord, telegram).await?;
+    println!("Test notification sent");
+    Ok(())
+}
+
+pub async fn replay(cli: &Cli, args: &ReplayArgs) -> Result<()> {
+    let content = fs::read_to_string(&args.payload)
+        .await
+        .map_err(|e| anyhow!("Could not read {}: {e}", args.payload.display()))?;
+    let event = match args.source {
+        ReplaySource::Sonarr => WebhookEvent::Sonarr(InstanceEvent {
+            instance: args.instance.clone(),
+            event: serde_json::from_str(&content)?,
+        }),
+        ReplaySource::Radarr => WebhookEvent::Radarr(serde_json::from_str(&content)?),
+        ReplaySource::Lidarr => WebhookEvent::Lidarr(serde_json::from_str(&content)?),
+        ReplaySource::Readarr => WebhookEvent::Readarr(serde_json::from_str(&content)?),
+        ReplaySource::Seerr => WebhookEvent::Seerr(serde_json::from_str(&content)?),
+    };
+
+    let (app_config, templates) = load_config(cli).await?;
+    let queue = open_queue(&app_config).await?;
+    queue.track_pushed().await;
+    let (discord, telegram) = (app_config.discord.clone(), app_config.telegram.clone());
+    controller::replay(app_config, templates, queue.clone(), event).await?;
+    notify::flush(queue, discord, telegram).await
+}
+
+pub async fn redrive(cli: &Cli, id: Option<&str>) -> Result<()> {
+    let app_config = AppConfig::load(cli.config.as_deref()).await?;
+    let queue = open_queue(&app_config).await?;
+    match id {
+        Some(id) => {
+            if !queue.redrive(id).await? {
+                return Err(anyhow!("Dead letter {id} not found"));
+            }
+            println!("Notification {id} queued again");
+        }
+        None => {
+            let count = queue.redrive_all().await?;
+            println!("{count} notifications queued again");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Result, anyhow};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use crate::config::DiscordConfig;
+
+#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr)]
+#[repr(u64)]
+pub enum EmbedColors {
+    Default = 0,
+    Aqua = 1752220,
+    Green = 3066993,
+    Blue = 3447003,
+    Purple = 10181046,
+    Gold = 15844367,
+    Orange = 15105570,
+    Red = 15158332,
+    Grey = 9807270,
+    DarkerGrey = 8359053,
+    Navy = 3426654,
+    DarkAqua = 1146986,
+    DarkGreen = 2067276,
+    DarkBlue = 2123412,
+    DarkPurple = 7419530,
+    DarkGold = 12745742,
+    DarkOrange = 11027200,
+    DarkRed = 10038562,
+    DarkGrey = 9936031,
+    LightGrey = 12370112,
+    DarkNavy = 2899536,
+    LuminousVividPink = 16580705,
+    DarkVividPink = 12320855,
+}
+
+impl EmbedColors {
+    const ALL: [(&'static str, Self); 23] = [
+        ("default", Self::Default),
+        ("aqua", Self::Aqua),
+        ("green", Self::Green),
+        ("blue", Self::Blue),
+        ("purple", Self::Purple),
+        ("gold", Self::Gold),
+        ("orange", Self::Orange),
+        ("red", Self::Red),
+        ("grey", Self::Grey),
+        ("darker_grey", Self::DarkerGrey),
+        ("navy", Self::Navy),
+        ("dark_aqua", Self::DarkAqua),
+        ("dark_green", Self::DarkGreen),
+        ("dark_blue", Self::DarkBlue),
+        ("dark_purple", Self::DarkPurple),
+        ("dark_gold", Self::DarkGold),
+        ("dark_orange", Self::DarkOrange),
+        ("dark_red", Self::DarkRed),
+        ("dark_grey", Self::DarkGrey),
+        ("light_grey", Self::LightGrey),
+        ("dark_navy", Self::DarkNavy),
+        ("luminous_vivid_pink", Self::LuminousVividPink),
+        ("dark_vivid_pink", Self::DarkVividPink),
+    ];
+
+    pub fn value(self) -> u64 {
+        self as u64
+    }
+}
+
+/// Parses a color given by its `EmbedColors` name, e.g. `DarkGreen` or `dark_green`, or as a
+/// `#RRGGBB` hex value
+pub fn parse_color(value: &str) -> Result<u64> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        return Some(hex)
+            .filter(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| anyhow!("Invalid hex color '{value}', expected #RRGGBB"));
+    }
+
+    let name = value
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    EmbedColors::ALL
+        .iter()
+        .find(|(color_name, _)| color_name.replace('_', "") == name)
+        .map(|(_, color)| color.value())
+        .ok_or_else(|| {
+            anyhow!(
+                "Unknown color '{value}', expected #RRGGBB or one of: {}",
+                EmbedColors::ALL.map(|(name, _)| name).join(", ")
+            )
+        })
+}
+
+/// Embed colors configured for Discord
+#[derive(Debug, Clone, Default)]
+pub struct DiscordColors {
+    default: Option<u64>,
+    by_type: BTreeMap<String, u64>,
+}
+
+impl DiscordColors {
+    /// The colors are checked when the configuration is loaded, so they are expected to be valid
+    pub fn new(config: &DiscordConfig) -> Self {
+        let default = config
+            .color
+            .as_deref()
+            .and_then(|color| parse_color(color).ok());
+        let by_type = config
+            .colors
+            .iter()
+            .filter_map(|(kind, color)| Some((kind.clone(), parse_color(color).ok()?)))
+            .collect();
+        Self { default, by_type }
+    }
+
+    /// Color configured for the notification type
+    pub fn for_type(&self, kind: &str) -> Option<u64> {
+        self.by_type.get(kind).copied()
+    }
+
+    /// Color configured for every notification
+    pub fn fallback(&self) -> Option<u64> {
+        self.default
+    }
+}
+
+/// Most common color of the image, the almost black and white pixels of borders and text are
+/// ignored so the color represents the artwork
+pub fn dominant_color(bytes: &[u8]) -> Result<u64> {
+    let image = image::load_from_memory(bytes)?.thumbnail(64, 64).to_rgb8();
+
+    // Similar colors are grouped keeping the 4 most significant bits of each channel
+    let mut buckets: HashMap<u16, (u64, [u64; 3])> = HashMap::new();
+    for pixel in image.pixels() {
+        let [r, g, b] = pixel.0;
+        let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+        if max < 24 || min > 232 {
+            continue;
+        }
+
+        let key = ((r as u16 >> 4) << 8) | ((g as u16 >> 4) << 4) | (b as u16 >> 4);
+        let (count, sums) = buckets.entry(key).or_default();
+        *count += 1;
+        for (sum, value) in sums.iter_mut().zip([r, g, b]) {
+            *sum += value as u64;
+        }
+    }
+
+    let (count, sums) = buckets
+        .into_values()
+        .max_by_key(|(count, _)| *count)
+        .ok_or_else(|| anyhow!("The image has no dominant color"))?;
+    let [r, g, b] = sums.map(|sum| sum / count);
+    Ok((r << 16) | (g << 8) | b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_color_hex() {
+        assert_eq!(parse_color("#00ff7F").unwrap(), 0x00ff7f);
+        assert_eq!(parse_color(" #000000 ").unwrap(), 0);
+        assert!(parse_color("#fff").is_err());
+        assert!(parse_color("#0000000").is_err());
+        assert!(parse_color("#+12345").is_err());
+        assert!(parse_color("#gggggg").is_err());
+    }
+
+    #[test]
+    fn parse_color_names() {
+        let dark_green = EmbedColors::DarkGreen.value();
+        assert_eq!(parse_color("DarkGreen").unwrap(), dark_green);
+        assert_eq!(parse_color("dark_green").unwrap(), dark_green);
+        assert_eq!(parse_color("DARK GREEN").unwrap(), dark_green);
+        assert_eq!(parse_color("red").unwrap(), EmbedColors::Red.value());
+        assert!(parse_color("greenish").is_err());
+        assert!(parse_color("").is_err());
+    }
+}
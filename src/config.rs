@@ -1,42 +1,436 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_aux::prelude::*;
+use serde_json::{Value, json};
+use tokio::fs;
+
+mod env;
+mod validate;
+
+/// Files looked up in the working directory when no configuration path is given
+const DEFAULT_CONFIG_FILES: [&str; 2] = ["config.yml", "config.yaml"];
+
+/// Keys holding secrets, documented in the schema with their `<key>_file` alternative
+const SECRET_KEYS: [&str; 3] = ["api_key", "bot_token", "webhook_url"];
+
+/// How the episode overviews are shown in the notifications
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EpisodeOverview {
+    Show,
+    #[default]
+    Spoiler,
+    Hide,
+}
+
+/// MiniJinja templates replacing the default parts of a message
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct MessageTemplate {
+    pub header: Option<String>,
+    pub title: Option<String>,
+    pub body: Option<String>,
+}
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct DiscordConfig {
     pub username: Option<String>,
     pub avatar_url: Option<String>,
     pub webhook_url: String,
+    /// Embed color, the name of a Discord color like `dark_green` or `#RRGGBB`
     pub color: Option<String>,
+    /// Embed colors by notification type, e.g. `media_available`
+    #[serde(default)]
+    pub colors: BTreeMap<String, String>,
+    /// Uses the dominant color of the poster when no color is configured for the type
+    #[serde(default, deserialize_with = "deserialize_bool_from_anything")]
+    pub poster_color: bool,
+    /// Sends a message when a request is pending approval, approved, declined or failed
+    #[serde(default, deserialize_with = "deserialize_bool_from_anything")]
+    pub request_status: bool,
+    #[serde(default)]
+    pub episode_overview: EpisodeOverview,
+    /// Minutes to wait for more episodes of the same season before notifying, 0 disables it
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub episode_digest_window: u64,
+    /// Updates the previous message of an ongoing season instead of sending a new one
+    #[serde(default, deserialize_with = "deserialize_bool_from_anything")]
+    pub edit_ongoing: bool,
+    /// Templates by notification type, e.g. `media_available`
+    #[serde(default)]
+    pub templates: BTreeMap<String, MessageTemplate>,
+    /// Language of the messages and of the media titles and overviews, the requester language
+    /// is used if not set
+    pub locale: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct TelegramConfig {
     pub bot_token: String,
+    #[serde(deserialize_with = "deserialize_string_from_number")]
+    #[schemars(with = "ChatId")]
     pub chat_id: String,
+    /// Sends a message when a request is pending approval, approved, declined or failed
+    #[serde(default, deserialize_with = "deserialize_bool_from_anything")]
+    pub request_status: bool,
+    #[serde(default)]
+    pub episode_overview: EpisodeOverview,
+    /// Minutes to wait for more episodes of the same season before notifying, 0 disables it
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub episode_digest_window: u64,
+    /// Updates the previous message of an ongoing season instead of sending a new one
+    #[serde(default, deserialize_with = "deserialize_bool_from_anything")]
+    pub edit_ongoing: bool,
+    /// Templates by notification type, e.g. `media_available`
+    #[serde(default)]
+    pub templates: BTreeMap<String, MessageTemplate>,
+    /// Language of the messages and of the media titles and overviews, the requester language
+    /// is used if not set
+    pub locale: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Chat id of the schema, Telegram ids can be written as numbers
+#[derive(JsonSchema)]
+#[schemars(untagged)]
+#[allow(dead_code)]
+enum ChatId {
+    Id(i64),
+    Username(String),
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct SonarrConfig {
+    pub name: Option<String>,
     pub url: String,
+    pub application_url: Option<String>,
     pub api_key: String,
+    #[serde(default, deserialize_with = "deserialize_bool_from_anything")]
+    pub is_4k: bool,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct RadarrConfig {
+    pub name: Option<String>,
+    pub url: String,
+    pub application_url: Option<String>,
+    pub api_key: String,
+    #[serde(default, deserialize_with = "deserialize_bool_from_anything")]
+    pub is_4k: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct LidarrConfig {
+    pub name: Option<String>,
+    pub url: String,
+    pub application_url: Option<String>,
+    pub api_key: String,
+}
+
+/// Maps the users that should be notified about new albums, Seerr does not handle music requests
+/// so they are matched against the artist MusicBrainz id/name or the artist tags in Lidarr
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MusicRequesterConfig {
+    pub display_name: String,
+    pub discord_id: Option<String>,
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub artists: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaServerKind {
+    Jellyfin,
+    Emby,
+    Plex,
+}
+
+fn default_media_server_poll_interval() -> u64 {
+    60
+}
+
+fn default_media_server_max_wait() -> u64 {
+    120
+}
+
+/// Media server used to confirm that the media is playable before notifying
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MediaServerConfig {
+    pub kind: MediaServerKind,
     pub url: String,
     pub api_key: String,
+    /// URL used in the notification links, defaults to `url`
+    pub public_url: Option<String>,
+    /// Seconds between library checks of the held notifications
+    #[serde(
+        default = "default_media_server_poll_interval",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub poll_interval: u64,
+    /// Minutes to hold a notification before sending it without a link
+    #[serde(
+        default = "default_media_server_max_wait",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_wait: u64,
+}
+
+fn default_queue_path() -> String {
+    "data/queue".to_string()
+}
+
+fn default_queue_max_attempts() -> u32 {
+    10
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+fn default_queue_retry_interval() -> u64 {
+    60
+}
+
+/// Storage of the outbound notifications until they are delivered
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct QueueConfig {
+    #[serde(default = "default_queue_path")]
+    pub path: String,
+    /// Failed attempts before a notification is moved to the dead letters
+    #[serde(
+        default = "default_queue_max_attempts",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_attempts: u32,
+    /// Seconds before the first retry, doubled on every failed attempt
+    #[serde(
+        default = "default_queue_retry_interval",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub retry_interval: u64,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            path: default_queue_path(),
+            max_attempts: default_queue_max_attempts(),
+            retry_interval: default_queue_retry_interval(),
+        }
+    }
+}
+
+fn default_webhooks_queue_capacity() -> usize {
+    1000
+}
+
+fn default_webhooks_workers() -> usize {
+    4
+}
+
+/// Buffering of the received webhook events until they are processed
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WebhooksConfig {
+    /// Events of each webhook waiting to be processed before the next ones are answered with a
+    /// 503, so the sender retries them later
+    #[serde(
+        default = "default_webhooks_queue_capacity",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub queue_capacity: usize,
+    /// Tasks processing the events concurrently, the events of the same media item are always
+    /// processed in order
+    #[serde(
+        default = "default_webhooks_workers",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub workers: usize,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            queue_capacity: default_webhooks_queue_capacity(),
+            workers: default_webhooks_workers(),
+        }
+    }
+}
+
+fn default_seerr_sync_interval() -> u64 {
+    30
+}
+
+fn default_seerr_cache_ttl() -> u64 {
+    60
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct SeerrConfig {
     pub url: String,
     pub api_key: String,
+    /// Minutes between two syncs of the requests, which only fetch the ones modified since
+    /// the previous sync
+    #[serde(
+        default = "default_seerr_sync_interval",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub sync_interval: u64,
+    /// Minutes the users and the media details fetched from Seerr are cached, 0 disables the
+    /// cache
+    #[serde(
+        default = "default_seerr_cache_ttl",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub cache_ttl: u64,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct AppConfig {
     pub seerr: SeerrConfig,
     pub discord: Option<DiscordConfig>,
     pub telegram: Option<TelegramConfig>,
     pub sonarr: Option<Vec<SonarrConfig>>,
     pub radarr: Option<Vec<RadarrConfig>>,
+    pub lidarr: Option<Vec<LidarrConfig>>,
+    pub music_requesters: Option<Vec<MusicRequesterConfig>>,
+    pub media_server: Option<MediaServerConfig>,
+    #[serde(default)]
+    pub queue: QueueConfig,
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
+}
+
+impl AppConfig {
+    /// Configuration file to read, `config.yml` or `config.yaml` of the working directory if no
+    /// path is given
+    pub fn find_file(path: Option<&Path>) -> Option<PathBuf> {
+        match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => DEFAULT_CONFIG_FILES
+                .iter()
+                .map(PathBuf::from)
+                .find(|path| path.is_file()),
+        }
+    }
+
+    /// Reads the configuration file found by [`AppConfig::find_file`]
+    ///
+    /// The `${NAME}` references are replaced by environment variables, the `<key>_file` entries
+    /// by the content of the file and the `INFORMARR__<PATH>` variables override the file
+    /// values, the file can be omitted if every required value is given by them.
+    pub async fn load(path: Option<&Path>) -> Result<Self> {
+        let (source, content) = match Self::find_file(path) {
+            Some(path) => {
+                let content = fs::read_to_string(&path)
+                    .await
+                    .map_err(|e| anyhow!("Could not read {}: {e}", path.display()))?;
+                (path.display().to_string(), Some(content))
+            }
+            None if env::has_overrides() => ("environment".to_string(), None),
+            None => {
+                return Err(anyhow!(
+                    "Could not find config.yml or config.yaml files nor INFORMARR__ variables"
+                ));
+            }
+        };
+
+        let mut value = match &content {
+            Some(content) => serde_yaml::from_str::<serde_yaml::Value>(content)
+                .map_err(|e| anyhow!("Could not parse config {source}: {e}"))?,
+            None => serde_yaml::Value::Null,
+        };
+        env::apply_overrides(&mut value)?;
+        env::interpolate(&mut value, "")?;
+        env::resolve_files(&mut value, "").await?;
+
+        validate::parse(value, content.as_deref())
+            .map_err(|e| anyhow!("Could not load config {source}: {e}"))
+    }
+
+    /// JSON Schema of the configuration file, editors use it to autocomplete and check the file
+    ///
+    /// The schema also accepts what is resolved before deserializing, the `<key>_file` entries
+    /// of the secrets and the `${NAME}` references in the numbers and booleans.
+    pub fn json_schema() -> Value {
+        let mut schema = serde_json::to_value(schemars::schema_for!(AppConfig)).unwrap_or_default();
+        relax_schema(&mut schema);
+        schema
+    }
+}
+
+fn relax_schema(node: &mut Value) {
+    match node {
+        Value::Object(object) => {
+            let mut secrets = Vec::new();
+            if let Some(Value::Object(properties)) = object.get_mut("properties") {
+                properties.values_mut().for_each(accept_text);
+                for key in SECRET_KEYS
+                    .iter()
+                    .filter(|key| properties.contains_key(**key))
+                {
+                    let file_key = format!("{key}{}", env::FILE_SUFFIX);
+                    properties.insert(
+                        file_key.clone(),
+                        json!({
+                            "description": format!("File containing the `{key}`"),
+                            "type": "string",
+                        }),
+                    );
+                    secrets.push((key.to_string(), file_key));
+                }
+            }
+
+            // The secret is required either directly or by its file
+            if let Some(Value::Array(required)) = object.get_mut("required") {
+                secrets.retain(|(key, _)| required.contains(&json!(key)));
+                required.retain(|key| !secrets.iter().any(|(secret, _)| key == secret));
+            } else {
+                secrets.clear();
+            }
+            if !secrets.is_empty() {
+                let alternatives = secrets.into_iter().map(|(key, file_key)| {
+                    json!({ "oneOf": [{ "required": [key] }, { "required": [file_key] }] })
+                });
+                if let Value::Array(all_of) = object.entry("allOf").or_insert_with(|| json!([])) {
+                    all_of.extend(alternatives);
+                }
+            }
+
+            object.values_mut().for_each(relax_schema);
+        }
+        Value::Array(items) => items.iter_mut().for_each(relax_schema),
+        _ => {}
+    }
+}
+
+/// Lets the numbers and booleans be given as text, like the `deserialize_*_from_*` helpers do
+fn accept_text(property: &mut Value) {
+    let Value::Object(property) = property else {
+        return;
+    };
+    let is_scalar = |kind: &Value| matches!(kind.as_str(), Some("integer" | "number" | "boolean"));
+    match property.get_mut("type") {
+        Some(kind) if is_scalar(kind) => *kind = json!([kind.clone(), "string"]),
+        Some(Value::Array(kinds))
+            if kinds.iter().any(is_scalar) && !kinds.contains(&json!("string")) =>
+        {
+            kinds.push(json!("string"));
+        }
+        _ => {}
+    }
 }
@@ -0,0 +1,253 @@
+use anyhow::{Result, anyhow};
+use serde_yaml::{Mapping, Value};
+use tokio::fs;
+
+/// Prefix of the variables that set configuration values, the path segments are separated by
+/// two underscores, e.g. `INFORMARR__SEERR__URL` or `INFORMARR__SONARR__0__API_KEY`
+const OVERRIDE_PREFIX: &str = "INFORMARR__";
+
+/// Suffix of the keys whose value is read from a file, e.g. `api_key_file`
+pub const FILE_SUFFIX: &str = "_file";
+
+/// Whether any configuration value is given by the environment
+pub fn has_overrides() -> bool {
+    std::env::vars().any(|(name, _)| name.starts_with(OVERRIDE_PREFIX))
+}
+
+/// Sets the values given by the `INFORMARR__` variables, they take precedence over the file
+///
+/// The values are kept as text, the numbers and booleans are converted when the configuration
+/// is deserialized.
+pub fn apply_overrides(config: &mut Value) -> Result<()> {
+    let mut variables = std::env::vars()
+        .filter_map(|(name, value)| {
+            let path = name.strip_prefix(OVERRIDE_PREFIX)?.to_lowercase();
+            Some((path, value))
+        })
+        .collect::<Vec<(String, String)>>();
+    variables.sort();
+
+    for (path, value) in variables {
+        let segments = path.split("__").collect::<Vec<&str>>();
+        if segments.iter().any(|segment| segment.is_empty()) {
+            return Err(anyhow!(
+                "Invalid configuration variable {OVERRIDE_PREFIX}{}",
+                path.to_uppercase()
+            ));
+        }
+        set_path(config, &segments, Value::String(value)).map_err(|e| {
+            anyhow!(
+                "Could not apply {OVERRIDE_PREFIX}{}: {e}",
+                path.to_uppercase()
+            )
+        })?;
+    }
+    Ok(())
+}
+
+fn set_path(node: &mut Value, segments: &[&str], value: Value) -> Result<()> {
+    let Some((segment, rest)) = segments.split_first() else {
+        *node = value;
+        return Ok(());
+    };
+
+    let index = segment.parse::<usize>().ok();
+    if node.is_null() {
+        *node = match index {
+            Some(_) => Value::Sequence(Vec::new()),
+            None => Value::Mapping(Mapping::new()),
+        };
+    }
+
+    let child = match (node, index) {
+        (Value::Sequence(items), Some(index)) => {
+            if items.len() <= index {
+                items.resize(index + 1, Value::Null);
+            }
+            &mut items[index]
+        }
+        (Value::Mapping(map), _) => {
+            // Overriding `<key>` replaces a `<key>_file` of the configuration file, and the
+            // other way around
+            if rest.is_empty() {
+                let counterpart = match segment.strip_suffix(FILE_SUFFIX) {
+                    Some(key) => key.to_string(),
+                    None => format!("{segment}{FILE_SUFFIX}"),
+                };
+                map.remove(counterpart.as_str());
+            }
+            map.entry(Value::String(segment.to_string()))
+                .or_insert(Value::Null)
+        }
+        _ => return Err(anyhow!("'{segment}' does not match the type of its parent")),
+    };
+    set_path(child, rest, value)
+}
+
+/// Replaces the `${NAME}` references in the string values by the environment variable,
+/// `${NAME:-default}` gives a value for when it is not set and `$${` writes a literal `${`
+pub fn interpolate(node: &mut Value, path: &str) -> Result<()> {
+    match node {
+        Value::String(text) => *text = interpolate_str(text, path)?,
+        Value::Sequence(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                interpolate(item, &format!("{path}[{index}]"))?;
+            }
+        }
+        Value::Mapping(map) => {
+            for (key, value) in map.iter_mut() {
+                interpolate(value, &child_path(path, key))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn interpolate_str(text: &str, path: &str) -> Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            result.push_str(&rest[..start - 1]);
+            result.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("Unclosed variable reference in {path}"))?;
+        let reference = &rest[start + 2..start + end];
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+
+        match (std::env::var(name.trim()), default) {
+            (Ok(value), _) => result.push_str(&value),
+            (Err(_), Some(default)) => result.push_str(default),
+            (Err(_), None) => {
+                return Err(anyhow!(
+                    "Environment variable {} used in {path} is not set",
+                    name.trim()
+                ));
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Replaces the `<key>_file` entries by `<key>` with the content of the file, so secrets like
+/// `api_key_file: /run/secrets/seerr` do not have to be written in the configuration
+pub async fn resolve_files(node: &mut Value, path: &str) -> Result<()> {
+    match node {
+        Value::Sequence(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                Box::pin(resolve_files(item, &format!("{path}[{index}]"))).await?;
+            }
+        }
+        Value::Mapping(map) => {
+            let file_keys = map
+                .keys()
+                .filter_map(|key| key.as_str())
+                .filter(|key| key.ends_with(FILE_SUFFIX))
+                .map(|key| key.to_string())
+                .collect::<Vec<String>>();
+
+            for file_key in file_keys {
+                let key = file_key
+                    .strip_suffix(FILE_SUFFIX)
+                    .unwrap_or_default()
+                    .to_string();
+                let key_path = child_path(path, &Value::String(key.clone()));
+                if map.contains_key(key.as_str()) {
+                    return Err(anyhow!(
+                        "Both {key_path} and {key_path}{FILE_SUFFIX} are set"
+                    ));
+                }
+
+                let Some(Value::String(file)) = map.remove(file_key.as_str()) else {
+                    return Err(anyhow!("{key_path}{FILE_SUFFIX} must be a file path"));
+                };
+                let content = fs::read_to_string(&file).await.map_err(|e| {
+                    anyhow!("Could not read {file} given in {key_path}{FILE_SUFFIX}: {e}")
+                })?;
+                let content = content.trim_end_matches(['\r', '\n']).to_string();
+                map.insert(Value::String(key), Value::String(content));
+            }
+
+            for (key, value) in map.iter_mut() {
+                Box::pin(resolve_files(value, &child_path(path, key))).await?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn child_path(path: &str, key: &Value) -> String {
+    let key = key.as_str().unwrap_or("?");
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolate_str_escapes_and_defaults() {
+        assert_eq!(interpolate_str("a$${B}c", "key").unwrap(), "a${B}c");
+        assert_eq!(
+            interpolate_str("a${INFORMARR_TEST_UNSET:-}c", "key").unwrap(),
+            "ac"
+        );
+        assert_eq!(
+            interpolate_str("${INFORMARR_TEST_UNSET:-http://localhost}", "key").unwrap(),
+            "http://localhost"
+        );
+        assert!(interpolate_str("${INFORMARR_TEST_UNSET}", "key").is_err());
+        assert!(interpolate_str("${INFORMARR_TEST_UNSET", "key").is_err());
+    }
+
+    #[test]
+    fn set_path_pads_sequences() {
+        let mut config = Value::Null;
+        set_path(
+            &mut config,
+            &["sonarr", "1", "api_key"],
+            Value::String("key".into()),
+        )
+        .unwrap();
+
+        let expected: Value = serde_yaml::from_str("sonarr: [null, {api_key: key}]").unwrap();
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn set_path_replaces_the_file_counterpart() {
+        let mut config: Value = serde_yaml::from_str("seerr: {api_key_file: /secret}").unwrap();
+        set_path(
+            &mut config,
+            &["seerr", "api_key"],
+            Value::String("key".into()),
+        )
+        .unwrap();
+
+        let expected: Value = serde_yaml::from_str("seerr: {api_key: key}").unwrap();
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn set_path_rejects_mismatched_types() {
+        let mut config: Value = serde_yaml::from_str("sonarr: [http://sonarr]").unwrap();
+        assert!(set_path(&mut config, &["sonarr", "name"], Value::String("x".into())).is_err());
+    }
+}
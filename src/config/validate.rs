@@ -0,0 +1,525 @@
+use std::fmt::{self, Display, Write as _};
+
+use anyhow::{Result, anyhow};
+use reqwest::Url;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_path_to_error::Segment;
+use serde_yaml::Value;
+
+use crate::{colors::parse_color, config::AppConfig, templates::NOTIFICATION_TYPES};
+
+/// Error raised on the value whose line is looked up
+const LOCATED: &str = "located value";
+
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Location of a value in the configuration, e.g. `sonarr[0].url`
+#[derive(Debug, Clone, Default)]
+struct ConfigPath(Vec<PathSegment>);
+
+impl ConfigPath {
+    fn key(&self, key: &str) -> Self {
+        let mut path = self.clone();
+        path.0.push(PathSegment::Key(key.to_string()));
+        path
+    }
+
+    fn index(&self, index: usize) -> Self {
+        let mut path = self.clone();
+        path.0.push(PathSegment::Index(index));
+        path
+    }
+}
+
+impl Display for ConfigPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "<root>");
+        }
+        for (position, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if position == 0 => write!(f, "{key}")?,
+                PathSegment::Key(key) => write!(f, ".{key}")?,
+                PathSegment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Problem found in the configuration
+#[derive(Debug)]
+struct ConfigIssue {
+    path: ConfigPath,
+    message: String,
+}
+
+/// Deserializes the configuration and checks its values, every problem is reported with its
+/// path and, when the configuration comes from a file, its line
+pub fn parse(value: Value, content: Option<&str>) -> Result<AppConfig> {
+    let config = serde_path_to_error::deserialize::<_, AppConfig>(value).map_err(|e| {
+        let mut path = ConfigPath(
+            e.path()
+                .iter()
+                .filter_map(|segment| match segment {
+                    Segment::Seq { index } => Some(PathSegment::Index(*index)),
+                    Segment::Map { key } => Some(PathSegment::Key(key.clone())),
+                    Segment::Enum { .. } | Segment::Unknown => None,
+                })
+                .collect(),
+        );
+        let message = e.inner().to_string();
+
+        // The unknown keys are reported on their parent
+        if let Some(key) = message
+            .strip_prefix("unknown field `")
+            .and_then(|rest| rest.split('`').next())
+            && path.0.last() != Some(&PathSegment::Key(key.to_string()))
+        {
+            path = path.key(key);
+        }
+
+        issues_error(&[ConfigIssue { path, message }], content)
+    })?;
+
+    let issues = check(&config);
+    if !issues.is_empty() {
+        return Err(issues_error(&issues, content));
+    }
+    Ok(config)
+}
+
+fn issues_error(issues: &[ConfigIssue], content: Option<&str>) -> anyhow::Error {
+    let mut message = String::from("Invalid configuration:");
+    for issue in issues {
+        let line = content.and_then(|content| {
+            find_line(content, &issue.path).or_else(|| {
+                // The value may have been read from a `<key>_file` entry
+                let mut path = issue.path.clone();
+                match path.0.last_mut() {
+                    Some(PathSegment::Key(key)) => key.push_str("_file"),
+                    _ => return None,
+                }
+                find_line(content, &path)
+            })
+        });
+        let _ = match line {
+            Some(line) => write!(
+                message,
+                "\n  {} (line {line}): {}",
+                issue.path, issue.message
+            ),
+            None => write!(message, "\n  {}: {}", issue.path, issue.message),
+        };
+    }
+    anyhow!(message)
+}
+
+/// Checks the values that deserialize correctly but cannot work
+fn check(config: &AppConfig) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    let mut issue = |path: ConfigPath, message: String| issues.push(ConfigIssue { path, message });
+    let root = ConfigPath::default();
+
+    let seerr = root.key("seerr");
+    check_url(&mut issue, seerr.key("url"), &config.seerr.url);
+    check_not_empty(&mut issue, seerr.key("api_key"), &config.seerr.api_key);
+    if config.seerr.sync_interval == 0 {
+        issue(
+            seerr.key("sync_interval"),
+            "must be greater than 0".to_string(),
+        );
+    }
+
+    if let Some(discord) = &config.discord {
+        let path = root.key("discord");
+        check_url(&mut issue, path.key("webhook_url"), &discord.webhook_url);
+        if let Some(avatar_url) = &discord.avatar_url {
+            check_url(&mut issue, path.key("avatar_url"), avatar_url);
+        }
+        if let Some(color) = &discord.color
+            && let Err(e) = parse_color(color)
+        {
+            issue(path.key("color"), e.to_string());
+        }
+        for (kind, color) in &discord.colors {
+            let color_path = path.key("colors").key(kind);
+            if !NOTIFICATION_TYPES.contains(&kind.as_str()) {
+                issue(
+                    color_path,
+                    format!(
+                        "unknown notification type, expected one of: {}",
+                        NOTIFICATION_TYPES.join(", ")
+                    ),
+                );
+            } else if let Err(e) = parse_color(color) {
+                issue(color_path, e.to_string());
+            }
+        }
+    }
+
+    if let Some(telegram) = &config.telegram {
+        let path = root.key("telegram");
+        if !is_telegram_token(&telegram.bot_token) {
+            issue(
+                path.key("bot_token"),
+                "invalid bot token, expected the `<bot id>:<secret>` token given by BotFather"
+                    .to_string(),
+            );
+        }
+        if !is_telegram_chat_id(&telegram.chat_id) {
+            issue(
+                path.key("chat_id"),
+                "invalid chat id, expected a number or a `@channel` username".to_string(),
+            );
+        }
+    }
+
+    if let Some(instances) = &config.sonarr {
+        let path = root.key("sonarr");
+        for (index, instance) in instances.iter().enumerate() {
+            let path = path.index(index);
+            check_url(&mut issue, path.key("url"), &instance.url);
+            if let Some(application_url) = &instance.application_url {
+                check_url(&mut issue, path.key("application_url"), application_url);
+            }
+            check_not_empty(&mut issue, path.key("api_key"), &instance.api_key);
+        }
+        check_names(
+            &mut issue,
+            &path,
+            instances.iter().map(|i| i.name.as_deref()),
+        );
+    }
+    if let Some(instances) = &config.radarr {
+        let path = root.key("radarr");
+        for (index, instance) in instances.iter().enumerate() {
+            let path = path.index(index);
+            check_url(&mut issue, path.key("url"), &instance.url);
+            if let Some(application_url) = &instance.application_url {
+                check_url(&mut issue, path.key("application_url"), application_url);
+            }
+            check_not_empty(&mut issue, path.key("api_key"), &instance.api_key);
+        }
+        check_names(
+            &mut issue,
+            &path,
+            instances.iter().map(|i| i.name.as_deref()),
+        );
+    }
+    if let Some(instances) = &config.lidarr {
+        let path = root.key("lidarr");
+        for (index, instance) in instances.iter().enumerate() {
+            let path = path.index(index);
+            check_url(&mut issue, path.key("url"), &instance.url);
+            if let Some(application_url) = &instance.application_url {
+                check_url(&mut issue, path.key("application_url"), application_url);
+            }
+            check_not_empty(&mut issue, path.key("api_key"), &instance.api_key);
+        }
+        check_names(
+            &mut issue,
+            &path,
+            instances.iter().map(|i| i.name.as_deref()),
+        );
+    }
+
+    if let Some(media_server) = &config.media_server {
+        let path = root.key("media_server");
+        check_url(&mut issue, path.key("url"), &media_server.url);
+        if let Some(public_url) = &media_server.public_url {
+            check_url(&mut issue, path.key("public_url"), public_url);
+        }
+        check_not_empty(&mut issue, path.key("api_key"), &media_server.api_key);
+        if media_server.poll_interval == 0 {
+            issue(
+                path.key("poll_interval"),
+                "must be greater than 0".to_string(),
+            );
+        }
+    }
+
+    if config.queue.max_attempts == 0 {
+        issue(
+            root.key("queue").key("max_attempts"),
+            "must be greater than 0".to_string(),
+        );
+    }
+
+    if config.webhooks.queue_capacity == 0 {
+        issue(
+            root.key("webhooks").key("queue_capacity"),
+            "must be greater than 0".to_string(),
+        );
+    }
+    if config.webhooks.workers == 0 {
+        issue(
+            root.key("webhooks").key("workers"),
+            "must be greater than 0".to_string(),
+        );
+    }
+
+    issues
+}
+
+fn check_url(issue: &mut impl FnMut(ConfigPath, String), path: ConfigPath, value: &str) {
+    match Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {}
+        Ok(_) => issue(path, format!("'{value}' is not an http or https URL")),
+        Err(e) => issue(path, format!("invalid URL '{value}': {e}")),
+    }
+}
+
+fn check_not_empty(issue: &mut impl FnMut(ConfigPath, String), path: ConfigPath, value: &str) {
+    if value.trim().is_empty() {
+        issue(path, "must not be empty".to_string());
+    }
+}
+
+/// The instance names select the instance in the webhook paths, so they must be unique
+fn check_names<'a>(
+    issue: &mut impl FnMut(ConfigPath, String),
+    path: &ConfigPath,
+    names: impl Iterator<Item = Option<&'a str>>,
+) {
+    let mut seen: Vec<String> = Vec::new();
+    for (index, name) in names.enumerate() {
+        let Some(name) = name else {
+            continue;
+        };
+        let name = name.trim().to_lowercase();
+        if seen.contains(&name) {
+            issue(
+                path.index(index).key("name"),
+                format!("duplicated instance name '{name}'"),
+            );
+        }
+        seen.push(name);
+    }
+}
+
+fn is_telegram_token(token: &str) -> bool {
+    let Some((bot_id, secret)) = token.split_once(':') else {
+        return false;
+    };
+    !bot_id.is_empty()
+        && bot_id.chars().all(|c| c.is_ascii_digit())
+        && secret.len() >= 30
+        && secret
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn is_telegram_chat_id(chat_id: &str) -> bool {
+    let chat_id = chat_id.trim();
+    match chat_id.strip_prefix('@') {
+        Some(username) => !username.is_empty(),
+        None => {
+            let digits = chat_id.strip_prefix('-').unwrap_or(chat_id);
+            !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+        }
+    }
+}
+
+/// Line of the value in the YAML document
+///
+/// The document is walked down to the value, which is then rejected on purpose so serde_yaml
+/// reports where it is. The keys are located on their own line rather than on their value.
+fn find_line(content: &str, path: &ConfigPath) -> Option<usize> {
+    let error = Locate(&path.0)
+        .deserialize(serde_yaml::Deserializer::from_str(content))
+        .err()?;
+    error.location().map(|location| location.line())
+}
+
+/// Walks down to the rest of the path, or rejects the value once the path is empty
+struct Locate<'a>(&'a [PathSegment]);
+
+impl<'de> DeserializeSeed<'de> for Locate<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        if self.0.is_empty() {
+            deserializer.deserialize_any(Located)
+        } else {
+            deserializer.deserialize_any(self)
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for Locate<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a mapping or a sequence")
+    }
+
+    // The scalars end the walk without finding the path
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let Some((PathSegment::Key(key), rest)) = self.0.split_first() else {
+            while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+            return Ok(());
+        };
+        let seed = LocateKey {
+            key,
+            is_last: rest.is_empty(),
+        };
+        while let Some(is_key) = map.next_key_seed(seed)? {
+            if is_key {
+                map.next_value_seed(Locate(rest))?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let Some((PathSegment::Index(index), rest)) = self.0.split_first() else {
+            while seq.next_element::<IgnoredAny>()?.is_some() {}
+            return Ok(());
+        };
+        for position in 0.. {
+            let element = if position == *index {
+                seq.next_element_seed(Locate(rest))?
+            } else {
+                seq.next_element::<IgnoredAny>()?.map(|_| ())
+            };
+            if element.is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tells whether the mapping key is the one of the path, the last key of the path is rejected
+#[derive(Clone, Copy)]
+struct LocateKey<'a> {
+    key: &'a str,
+    is_last: bool,
+}
+
+impl<'de> DeserializeSeed<'de> for LocateKey<'_> {
+    type Value = bool;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<bool, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for LocateKey<'_> {
+    type Value = bool;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a key")
+    }
+
+    fn visit_str<E: de::Error>(self, key: &str) -> Result<bool, E> {
+        match key == self.key {
+            true if self.is_last => Err(E::custom(LOCATED)),
+            is_key => Ok(is_key),
+        }
+    }
+}
+
+/// Rejects any value
+struct Located;
+
+impl<'de> Visitor<'de> for Located {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{LOCATED}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+seerr:
+  url: http://seerr
+  api_key: key
+sonarr:
+  - name: Main
+    url: http://sonarr
+  - name: 4k
+    url: http://sonarr-4k
+";
+
+    fn names_issues(names: &[Option<&str>]) -> Vec<String> {
+        let mut issues = Vec::new();
+        let path = ConfigPath::default().key("sonarr");
+        check_names(
+            &mut |path, message| issues.push(format!("{path}: {message}")),
+            &path,
+            names.iter().copied(),
+        );
+        issues
+    }
+
+    #[test]
+    fn check_names_reports_duplicates() {
+        assert!(names_issues(&[Some("main"), Some("4k"), None, None]).is_empty());
+        assert_eq!(
+            names_issues(&[Some("Main"), None, Some(" main ")]),
+            ["sonarr[2].name: duplicated instance name 'main'"]
+        );
+    }
+
+    #[test]
+    fn find_line_of_keys_and_items() {
+        let root = ConfigPath::default();
+        assert_eq!(find_line(CONFIG, &root.key("seerr")), Some(1));
+        assert_eq!(
+            find_line(CONFIG, &root.key("seerr").key("api_key")),
+            Some(3)
+        );
+        assert_eq!(
+            find_line(CONFIG, &root.key("sonarr").index(1).key("url")),
+            Some(8)
+        );
+        assert_eq!(find_line(CONFIG, &root.key("sonarr").index(1)), Some(7));
+    }
+
+    #[test]
+    fn find_line_of_missing_values() {
+        let root = ConfigPath::default();
+        assert_eq!(find_line(CONFIG, &root.key("radarr")), None);
+        assert_eq!(find_line(CONFIG, &root.key("sonarr").index(2)), None);
+        assert_eq!(
+            find_line(CONFIG, &root.key("seerr").key("url").key("host")),
+            None
+        );
+    }
+}
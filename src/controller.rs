@@ -1,67 +1,271 @@
-use std::time::Duration;
+use std::{
+    collections::{BTreeMap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
 
 use anyhow::{Context, Result, anyhow};
 use bon::Builder;
+use lidarr::{
+    apis::{Api as _, album_api::ApiV1AlbumIdGetParams},
+    models::LidarrAlbumResource,
+};
 use log::{debug, error, info, trace, warn};
-use radarr::apis::Api as _;
+use radarr::{
+    apis::{Api as _, movie_api::ApiV3MovieIdGetParams},
+    models::RadarrMovieResource,
+};
 use reqwest::Url;
 use seerr::{
     apis::{
-        Api as _, movies_api::MovieMovieIdGetParams, request_api::RequestRequestIdGetParams,
-        tv_api::TvTvIdGetParams, users_api::UserUserIdGetParams,
+        Api as _,
+        movies_api::MovieMovieIdGetParams,
+        request_api::RequestRequestIdGetParams,
+        tv_api::{TvTvIdGetParams, TvTvIdSeasonSeasonNumberGetParams},
+        users_api::UserUserIdGetParams,
     },
     models::{SeerrMediaRequest, SeerrMovieDetails, SeerrTvDetails},
 };
 use serde_json::{Value, json};
-use serde_repr::{Deserialize_repr, Serialize_repr};
 use sonarr::{
     apis::{Api as _, series_api::ApiV3SeriesIdGetParams},
-    models::SonarrSeasonResource,
+    models::{SonarrSeasonResource, SonarrSeriesResource},
 };
 use time::{OffsetDateTime, format_description::well_known::Rfc3339};
 use tokio::{
-    sync::{mpsc, watch},
+    sync::{Notify, mpsc, watch},
+    task::{JoinHandle, JoinSet},
     time::Instant,
 };
 
 use crate::{
-    config::{AppConfig, DiscordConfig, RadarrConfig, SonarrConfig, TelegramConfig},
-    webhooks::{self, radarr::RadarrEvent, seerr::SeerrEvent, sonarr::SonarrEvent},
+    cache::MetadataCache,
+    colors::{DiscordColors, EmbedColors, dominant_color},
+    config::{
+        AppConfig, DiscordConfig, EpisodeOverview, MusicRequesterConfig, RadarrConfig,
+        SonarrConfig, TelegramConfig,
+    },
+    health::{self, UpstreamHealth},
+    i18n::Locale,
+    mediaserver::{MediaItemQuery, MediaServerClient},
+    notify::{Destination, NotificationQueue, OutboundMessage, OutboundNotification},
+    reload::LoadedConfig,
+    templates::{MessageTemplates, TemplatePart},
+    webhooks::{
+        self, InstanceEvent,
+        lidarr::{self as lidarr_webhook, LidarrEvent},
+        mediaserver::MediaServerEvent,
+        radarr::{self as radarr_webhook, RadarrEvent},
+        readarr::{self as readarr_webhook, ReadarrEvent},
+        seerr::SeerrEvent,
+        sonarr::{self as sonarr_webhook, SonarrEvent},
+    },
 };
 
-#[derive(Debug, Clone, Serialize_repr, Deserialize_repr)]
-#[repr(u64)]
-enum EmbedColors {
-    Default = 0,
-    Aqua = 1752220,
-    Green = 3066993,
-    Blue = 3447003,
-    Purple = 10181046,
-    Gold = 15844367,
-    Orange = 15105570,
-    Red = 15158332,
-    Grey = 9807270,
-    DarkerGrey = 8359053,
-    Navy = 3426654,
-    DarkAqua = 1146986,
-    DarkGreen = 2067276,
-    DarkBlue = 2123412,
-    DarkPurple = 7419530,
-    DarkGold = 12745742,
-    DarkOrange = 11027200,
-    DarkRed = 10038562,
-    DarkGrey = 9936031,
-    LightGrey = 12370112,
-    DarkNavy = 2899536,
-    LuminousVividPink = 16580705,
-    DarkVividPink = 12320855,
-}
-
 #[derive(Debug, Clone, PartialEq)]
 enum NotificationType {
     MediaAvailable,
     OngoingSeasonAvailable,
     OngoingEpisodeAvailable,
+    EpisodeDigestAvailable,
+    AlbumAvailable,
+    BookAvailable,
+    RequestStatusChanged(RequestStatus),
+}
+
+impl NotificationType {
+    /// Name used to select the templates of the notification type
+    fn name(&self) -> &'static str {
+        match self {
+            Self::MediaAvailable => "media_available",
+            Self::OngoingSeasonAvailable => "ongoing_season_available",
+            Self::OngoingEpisodeAvailable => "ongoing_episode_available",
+            Self::EpisodeDigestAvailable => "episode_digest_available",
+            Self::AlbumAvailable => "album_available",
+            Self::BookAvailable => "book_available",
+            Self::RequestStatusChanged(_) => "request_status_changed",
+        }
+    }
+}
+
+/// Status of a request reported by the Seerr lifecycle webhooks
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RequestStatus {
+    Pending,
+    Processing,
+    Declined,
+    Failed,
+}
+
+impl RequestStatus {
+    fn from_seerr(notification_type: &webhooks::seerr::NotificationType) -> Option<Self> {
+        use webhooks::seerr::NotificationType as SeerrNotificationType;
+        match notification_type {
+            SeerrNotificationType::MediaPending => Some(Self::Pending),
+            SeerrNotificationType::MediaApproved | SeerrNotificationType::MediaAutoApproved => {
+                Some(Self::Processing)
+            }
+            SeerrNotificationType::MediaDeclined => Some(Self::Declined),
+            SeerrNotificationType::MediaFailed => Some(Self::Failed),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Processing => "processing",
+            Self::Declined => "declined",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn color(self) -> EmbedColors {
+        match self {
+            Self::Pending => EmbedColors::Orange,
+            Self::Processing => EmbedColors::Purple,
+            Self::Declined | Self::Failed => EmbedColors::Red,
+        }
+    }
+
+    fn header_key(self) -> String {
+        format!("request_{}", self.name())
+    }
+
+    fn text_key(self) -> String {
+        format!("status_{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct MovieMetadata {
+    runtime: Option<i32>,
+    certification: Option<String>,
+    imdb_rating: Option<f64>,
+    tmdb_rating: Option<f64>,
+}
+
+impl MovieMetadata {
+    fn from_radarr(movie: &RadarrMovieResource) -> Self {
+        let ratings = movie.ratings.as_deref();
+        Self {
+            runtime: movie.runtime.filter(|runtime| *runtime > 0),
+            certification: movie
+                .certification
+                .clone()
+                .flatten()
+                .filter(|certification| !certification.is_empty()),
+            imdb_rating: ratings
+                .and_then(|ratings| ratings.imdb.as_deref())
+                .and_then(|rating| rating.value)
+                .filter(|value| *value > 0.0),
+            tmdb_rating: ratings
+                .and_then(|ratings| ratings.tmdb.as_deref())
+                .and_then(|rating| rating.value)
+                .filter(|value| *value > 0.0),
+        }
+    }
+
+    fn runtime_string(&self) -> Option<String> {
+        self.runtime
+            .map(|runtime| match (runtime / 60, runtime % 60) {
+                (0, minutes) => format!("{minutes}m"),
+                (hours, 0) => format!("{hours}h"),
+                (hours, minutes) => format!("{hours}h {minutes}m"),
+            })
+    }
+
+    fn rating_string(&self) -> Option<String> {
+        let ratings = [("IMDb", self.imdb_rating), ("TMDB", self.tmdb_rating)]
+            .into_iter()
+            .filter_map(|(source, rating)| rating.map(|r| format!("{source} {r:.1}")))
+            .collect::<Vec<String>>();
+        if ratings.is_empty() {
+            None
+        } else {
+            Some(ratings.join(" | "))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AlbumMetadata {
+    artist: String,
+    album_type: Option<String>,
+    release_date: Option<String>,
+    track_count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum BookFormat {
+    Ebook,
+    Audiobook,
+}
+
+impl BookFormat {
+    const AUDIO_EXTENSIONS: [&str; 8] = ["mp3", "m4a", "m4b", "flac", "ogg", "opus", "aac", "wma"];
+
+    /// Guesses the format from the quality name reported by Readarr or the file extension
+    fn from_file(file: &readarr_webhook::BookFile) -> Self {
+        let extension = std::path::Path::new(&file.path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let quality = file.quality.as_deref().unwrap_or_default().to_lowercase();
+
+        let is_audio = Self::AUDIO_EXTENSIONS
+            .iter()
+            .any(|audio| extension == *audio || quality == *audio);
+        if is_audio {
+            Self::Audiobook
+        } else {
+            Self::Ebook
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Self::Ebook => "Ebook",
+            Self::Audiobook => "Audiobook",
+        }
+    }
+
+    fn text_key(&self) -> &str {
+        match self {
+            Self::Ebook => "format_ebook",
+            Self::Audiobook => "format_audiobook",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct BookMetadata {
+    author: String,
+    format: BookFormat,
+    release_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct EpisodeMetadata {
+    title: Option<String>,
+    overview: Option<String>,
+    air_date: Option<String>,
+    still_url: Option<Url>,
+}
+
+impl EpisodeMetadata {
+    /// Formats the overview following the destination preference, `spoiler` wraps the text
+    /// using the given markers
+    fn overview_text(&self, mode: &EpisodeOverview, spoiler: (&str, &str)) -> Option<String> {
+        let overview = self.overview.as_ref()?;
+        match mode {
+            EpisodeOverview::Show => Some(overview.clone()),
+            EpisodeOverview::Spoiler => Some(format!("{}{overview}{}", spoiler.0, spoiler.1)),
+            EpisodeOverview::Hide => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Builder)]
@@ -71,40 +275,454 @@ struct NotificationData {
     seasons: Option<Vec<i32>>,
     season_number: Option<i32>,
     episode_number: Option<i32>,
+    episodes: Option<Vec<i32>>,
+    movie_metadata: Option<MovieMetadata>,
+    album_metadata: Option<AlbumMetadata>,
+    book_metadata: Option<BookMetadata>,
+    episode_metadata: Option<EpisodeMetadata>,
+    play_url: Option<Url>,
+    /// Webhook that triggered the notification, exposed to the templates
+    payload: Option<Value>,
+    /// Whether the message edits the previous one of the season, exposed to the templates
+    #[builder(default)]
+    is_edit: bool,
+}
+
+impl NotificationData {
+    /// Values available to the message templates
+    fn template_context(&self, header: &str, title: &str, body: &str) -> Value {
+        let media_request = &self.media_request;
+        json!({
+            "type": self.r#type.name(),
+            "request_status": match self.r#type {
+                NotificationType::RequestStatusChanged(status) => Some(status.name()),
+                _ => None,
+            },
+            "media": {
+                "type": format!("{:?}", media_request.r#type).to_lowercase(),
+                "tmdb_id": media_request.media.tmdb_id,
+                "tvdb_id": media_request.media.tvdb_id,
+                "title": media_request.media.title,
+                "overview": media_request.media.overview,
+                "image_url": media_request.image_url.as_ref().map(|url| url.to_string()),
+                "is_4k": media_request.is_4k,
+            },
+            "requested_by": {
+                "display_name": media_request.requested_by.display_name,
+                "discord_id": media_request.requested_by.discord_id,
+            },
+            "seasons": self.seasons,
+            "season_number": self.season_number,
+            "episode_number": self.episode_number,
+            "episodes": self.episodes,
+            "movie": self.movie_metadata.as_ref().map(|metadata| json!({
+                "runtime": metadata.runtime_string(),
+                "certification": metadata.certification,
+                "imdb_rating": metadata.imdb_rating,
+                "tmdb_rating": metadata.tmdb_rating,
+            })),
+            "album": self.album_metadata.as_ref().map(|metadata| json!({
+                "artist": metadata.artist,
+                "album_type": metadata.album_type,
+                "release_date": metadata.release_date,
+                "track_count": metadata.track_count,
+            })),
+            "book": self.book_metadata.as_ref().map(|metadata| json!({
+                "author": metadata.author,
+                "format": metadata.format.name(),
+                "release_date": metadata.release_date,
+            })),
+            "episode": self.episode_metadata.as_ref().map(|metadata| json!({
+                "title": metadata.title,
+                "overview": metadata.overview,
+                "air_date": metadata.air_date,
+                "still_url": metadata.still_url.as_ref().map(|url| url.to_string()),
+            })),
+            "play_url": self.play_url.as_ref().map(|url| url.to_string()),
+            "payload": self.payload,
+            "is_edit": self.is_edit,
+            "default": {
+                "header": header,
+                "title": title,
+                "body": body,
+            },
+        })
+    }
+}
+
+/// Episodes of the same season collected for a destination until no more arrive
+struct EpisodeDigest {
+    destination: Destination,
+    data: NotificationData,
+    episodes: Vec<i32>,
+    last_received: Instant,
+}
+
+impl EpisodeDigest {
+    fn matches(&self, destination: Destination, data: &NotificationData) -> bool {
+        let media_request = &self.data.media_request;
+        self.destination == destination
+            && media_request.media.tmdb_id == data.media_request.media.tmdb_id
+            && media_request.is_4k == data.media_request.is_4k
+            && self.data.season_number == data.season_number
+    }
+
+    fn into_notification(self) -> NotificationData {
+        // A single episode keeps its detailed notification
+        if self.episodes.len() <= 1 {
+            return self.data;
+        }
+
+        let mut data = self.data;
+        data.r#type = NotificationType::EpisodeDigestAvailable;
+        data.episode_number = self.episodes.last().copied();
+        data.episodes = Some(self.episodes);
+        data.episode_metadata = None;
+        data
+    }
+}
+
+/// Digests shared by the workers, `added` wakes the controller loop to schedule their window
+#[derive(Default)]
+struct EpisodeDigests {
+    digests: Mutex<Vec<EpisodeDigest>>,
+    added: Notify,
+}
+
+/// Formats the episode numbers joining the consecutive ones, e.g. `3–8, 10`
+fn episode_ranges_string(episodes: &[i32]) -> String {
+    let mut ranges: Vec<(i32, i32)> = Vec::new();
+    for &episode in episodes {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == episode => *end = episode,
+            _ => ranges.push((episode, episode)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}–{end}")
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
 }
 
+/// Renders the notifications for each destination and queues them to be sent
 struct NotificationController {
     discord: Option<DiscordConfig>,
     telegram: Option<TelegramConfig>,
+    discord_colors: DiscordColors,
+    templates: MessageTemplates,
+    queue: Arc<NotificationQueue>,
+    digests: Arc<EpisodeDigests>,
+    client: reqwest::Client,
+    /// Dominant colors by poster URL
+    cache: Arc<MetadataCache>,
 }
 
 impl NotificationController {
-    fn new(discord: Option<DiscordConfig>, telegram: Option<TelegramConfig>) -> Self {
-        Self { discord, telegram }
+    fn new(
+        discord: Option<DiscordConfig>,
+        telegram: Option<TelegramConfig>,
+        templates: MessageTemplates,
+        queue: Arc<NotificationQueue>,
+        cache: Arc<MetadataCache>,
+    ) -> Self {
+        let discord_colors = discord.as_ref().map(DiscordColors::new).unwrap_or_default();
+
+        Self {
+            discord,
+            telegram,
+            discord_colors,
+            templates,
+            queue,
+            digests: Arc::default(),
+            client: reqwest::Client::new(),
+            cache,
+        }
+    }
+
+    /// Replaces the default header, title and body with the ones of the user templates
+    fn apply_templates(
+        &self,
+        destination: Destination,
+        data: &NotificationData,
+        parts: [String; 3],
+    ) -> [String; 3] {
+        let context = data.template_context(&parts[0], &parts[1], &parts[2]);
+        let template_parts = [
+            TemplatePart::Header,
+            TemplatePart::Title,
+            TemplatePart::Body,
+        ];
+        let mut result = parts;
+        for (text, part) in result.iter_mut().zip(template_parts) {
+            match self
+                .templates
+                .render(destination, data.r#type.name(), part, &context)
+            {
+                Some(Ok(rendered)) => *text = rendered,
+                Some(Err(e)) => warn!("{e}"),
+                None => {}
+            }
+        }
+        result
     }
 
     async fn send_notification(&self, notification_request: NotificationData) {
-        if let Err(e) = self.send_discord_notification(&notification_request).await {
-            warn!("Failed sending Discord message: {e}");
+        for destination in [Destination::Discord, Destination::Telegram] {
+            let is_status = matches!(
+                notification_request.r#type,
+                NotificationType::RequestStatusChanged(_)
+            );
+            if is_status && !self.request_status(destination) {
+                continue;
+            }
+
+            let is_episode =
+                notification_request.r#type == NotificationType::OngoingEpisodeAvailable;
+            if is_episode && self.digest_window(destination).is_some() {
+                self.add_to_digest(destination, notification_request.clone());
+            } else {
+                self.send_to(destination, &notification_request).await;
+            }
+        }
+    }
+
+    async fn send_to(&self, destination: Destination, data: &NotificationData) {
+        let is_ongoing = matches!(
+            data.r#type,
+            NotificationType::OngoingSeasonAvailable
+                | NotificationType::OngoingEpisodeAvailable
+                | NotificationType::EpisodeDigestAvailable
+        );
+
+        let mut data = data.clone();
+        if let Some(text) = self
+            .configured_locale(destination)
+            .and_then(|locale| data.media_request.media.translations.get(locale))
+        {
+            data.media_request.media.title = text.title.clone();
+            data.media_request.media.overview = text.overview.clone();
+        }
+
+        // The message of the season is edited with the latest episodes, a new one is sent when
+        // the season changes
+        let mut ongoing_key = None;
+        if is_ongoing && self.edit_ongoing(destination) {
+            data.is_edit = true;
+            ongoing_key = Some(format!(
+                "{destination:?}/{}/{}",
+                data.media_request.media.tmdb_id,
+                if data.media_request.is_4k { "4k" } else { "hd" }
+            ));
         }
 
-        if let Err(e) = self.send_telegram_notification(&notification_request).await {
-            warn!("Failed sending Telegram message: {e}");
+        let message = match destination {
+            Destination::Discord => {
+                let poster_color = self.poster_color(&data).await;
+                self.build_discord_message(&data, poster_color)
+            }
+            Destination::Telegram => self.build_telegram_message(&data),
+        };
+        let Some(message) = message else {
+            return;
+        };
+
+        let notification = OutboundNotification {
+            message,
+            ongoing_key,
+            season_number: data.season_number,
+        };
+        if let Err(e) = self.queue.push(notification).await {
+            warn!("Could not queue {destination:?} notification: {e}");
         }
     }
 
-    async fn send_telegram_notification(&self, data: &NotificationData) -> Result<()> {
-        let telegram = match self.telegram {
-            Some(ref config) => config,
-            None => return Ok(()),
+    /// Locale of the destination, or the one of the requester if not configured
+    fn locale(&self, destination: Destination, data: &NotificationData) -> Locale {
+        Locale::from_candidates([
+            self.configured_locale(destination),
+            data.media_request.requested_by.locale.as_deref(),
+        ])
+    }
+
+    fn configured_locale(&self, destination: Destination) -> Option<&str> {
+        match destination {
+            Destination::Discord => self.discord.as_ref().and_then(|c| c.locale.as_deref()),
+            Destination::Telegram => self.telegram.as_ref().and_then(|c| c.locale.as_deref()),
+        }
+    }
+
+    /// Locales configured on the destinations, the media details are also fetched in them
+    fn configured_locales(&self) -> Vec<&str> {
+        let mut locales = [Destination::Discord, Destination::Telegram]
+            .into_iter()
+            .filter_map(|destination| self.configured_locale(destination))
+            .collect::<Vec<&str>>();
+        locales.dedup();
+        locales
+    }
+
+    /// Dominant color of the poster when Discord is configured to use it and no other color
+    /// applies to the notification
+    async fn poster_color(&self, data: &NotificationData) -> Option<u64> {
+        let discord = self.discord.as_ref()?;
+        let is_status = matches!(data.r#type, NotificationType::RequestStatusChanged(_));
+        let has_type_color = self.discord_colors.for_type(data.r#type.name()).is_some();
+        if !discord.poster_color || is_status || has_type_color {
+            return None;
+        }
+
+        let url = data.media_request.image_url.as_ref()?.to_string();
+        match self
+            .cache
+            .poster_colors
+            .get_or_fetch(url.clone(), self.fetch_poster_color(&url))
+            .await
+        {
+            Ok(color) => Some(color),
+            Err(e) => {
+                warn!("Could not get the color of the poster {url}: {e}");
+                None
+            }
+        }
+    }
+
+    async fn fetch_poster_color(&self, url: &str) -> Result<u64> {
+        let bytes = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        tokio::task::spawn_blocking(move || dominant_color(&bytes)).await?
+    }
+
+    /// Whether the destination is notified of the request lifecycle
+    fn request_status(&self, destination: Destination) -> bool {
+        match destination {
+            Destination::Discord => self.discord.as_ref().is_some_and(|c| c.request_status),
+            Destination::Telegram => self.telegram.as_ref().is_some_and(|c| c.request_status),
+        }
+    }
+
+    /// Whether any destination is notified of the request lifecycle
+    fn notifies_request_status(&self) -> bool {
+        self.request_status(Destination::Discord) || self.request_status(Destination::Telegram)
+    }
+
+    fn edit_ongoing(&self, destination: Destination) -> bool {
+        match destination {
+            Destination::Discord => self.discord.as_ref().is_some_and(|c| c.edit_ongoing),
+            Destination::Telegram => self.telegram.as_ref().is_some_and(|c| c.edit_ongoing),
+        }
+    }
+
+    fn digest_window(&self, destination: Destination) -> Option<Duration> {
+        let minutes = match destination {
+            Destination::Discord => self.discord.as_ref()?.episode_digest_window,
+            Destination::Telegram => self.telegram.as_ref()?.episode_digest_window,
         };
+        Some(Duration::from_secs(minutes * 60)).filter(|window| !window.is_zero())
+    }
+
+    fn add_to_digest(&self, destination: Destination, data: NotificationData) {
+        let episode_number = data.episode_number.unwrap_or_default();
+        let mut digests = self.digests.digests.lock().unwrap();
+        match digests
+            .iter_mut()
+            .find(|digest| digest.matches(destination, &data))
+        {
+            Some(digest) => {
+                if !digest.episodes.contains(&episode_number) {
+                    digest.episodes.push(episode_number);
+                    digest.episodes.sort_unstable();
+                }
+                digest.last_received = Instant::now();
+            }
+            None => digests.push(EpisodeDigest {
+                destination,
+                data,
+                episodes: vec![episode_number],
+                last_received: Instant::now(),
+            }),
+        }
+        self.digests.added.notify_one();
+    }
+
+    /// Time at which the oldest digest window closes
+    fn next_digest_deadline(&self) -> Option<Instant> {
+        self.digests
+            .digests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|digest| {
+                self.digest_window(digest.destination)
+                    .map(|window| digest.last_received + window)
+            })
+            .min()
+    }
+
+    /// Sends every digest without waiting for its window to close
+    async fn flush_all_digests(&self) {
+        let digests = std::mem::take(&mut *self.digests.digests.lock().unwrap());
+        for digest in digests {
+            let destination = digest.destination;
+            self.send_to(destination, &digest.into_notification()).await;
+        }
+    }
+
+    /// Removes the digests that did not receive new episodes within their window
+    fn take_closed_digests(&self) -> Vec<EpisodeDigest> {
+        let mut digests = self.digests.digests.lock().unwrap();
+        let (closed, still_pending) = std::mem::take(&mut *digests)
+            .into_iter()
+            .partition::<Vec<EpisodeDigest>, _>(|digest| {
+                self.digest_window(digest.destination)
+                    .is_none_or(|window| digest.last_received.elapsed() >= window)
+            });
+        *digests = still_pending;
+        closed
+    }
+
+    async fn send_digests(&self, digests: Vec<EpisodeDigest>) {
+        for digest in digests {
+            let destination = digest.destination;
+            info!(
+                "Sending digest of {} episodes of {}",
+                digest.episodes.len(),
+                digest.data.media_request.media.title
+            );
+            self.send_to(destination, &digest.into_notification()).await;
+        }
+    }
+
+    fn build_telegram_message(&self, data: &NotificationData) -> Option<OutboundMessage> {
+        let telegram = self.telegram.as_ref()?;
+        let locale = self.locale(Destination::Telegram, data);
 
         let media_request = &data.media_request;
 
-        // Create a multipart form to send the photo
-        let mut form = reqwest::multipart::Form::new()
-            .text("chat_id", telegram.chat_id.clone())
-            .text("parse_mode", "HTML"); // Add the caption text
+        // Parameters of the multipart form, kept apart so the request can be retried
+        let mut params = vec![
+            ("chat_id", telegram.chat_id.clone()),
+            ("parse_mode", "HTML".to_string()),
+        ];
+
+        let quality_suffix = if media_request.is_4k {
+            locale.text("in_4k")
+        } else {
+            String::new()
+        };
 
         let prefix_text;
         let title;
@@ -122,26 +740,53 @@ impl NotificationController {
                     .join(", ");
 
                 if let Some(url) = &media_request.image_url {
-                    form = form.text("photo", url.to_string());
+                    params.push(("photo", url.to_string()));
                     is_photo = true;
                 }
-                prefix_text = "New Content Available";
-                let season_string = format!("Season {seasons_joined}");
+                prefix_text = format!("{}{quality_suffix}", locale.text("new_content_available"));
+                let season_string = locale.format("season", &[("season", &seasons_joined)]);
                 title = if seasons_joined.is_empty() {
                     media_request.media.title.clone()
                 } else {
                     format!("{} - {}", media_request.media.title, season_string)
                 };
-                message = media_request.media.overview.clone();
+                let metadata_lines = data
+                    .movie_metadata
+                    .as_ref()
+                    .map(|metadata| {
+                        [
+                            metadata
+                                .runtime_string()
+                                .map(|v| format!("<b>{}:</b> {v}", locale.text("label_runtime"))),
+                            metadata.certification.as_ref().map(|v| {
+                                format!("<b>{}:</b> {v}", locale.text("label_certification"))
+                            }),
+                            metadata
+                                .rating_string()
+                                .map(|v| format!("<b>{}:</b> {v}", locale.text("label_rating"))),
+                        ]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                    })
+                    .unwrap_or_default();
+                message = format!("{}\n\n{}", media_request.media.overview, metadata_lines)
+                    .trim()
+                    .to_string();
             }
             NotificationType::OngoingSeasonAvailable => {
-                prefix_text = "New Content Available";
-                let season_string = data
-                    .season_number
-                    .map_or(String::new(), |s| format!("Season {s}"));
-                let episode_string = data
-                    .episode_number
-                    .map_or(String::new(), |e| format!("Episode 1 to {e}"));
+                if let Some(url) = &media_request.image_url {
+                    params.push(("photo", url.to_string()));
+                    is_photo = true;
+                }
+                prefix_text = format!("{}{quality_suffix}", locale.text("new_content_available"));
+                let season_string = data.season_number.map_or(String::new(), |s| {
+                    locale.format("season", &[("season", &s)])
+                });
+                let episode_string = data.episode_number.map_or(String::new(), |e| {
+                    locale.format("episode_range", &[("episode", &e)])
+                });
                 title = format!(
                     "{} - {} {}",
                     media_request.media.title, season_string, episode_string
@@ -149,109 +794,218 @@ impl NotificationController {
                 message = media_request.media.overview.clone();
             }
             NotificationType::OngoingEpisodeAvailable => {
-                prefix_text = "New Episode Available";
-                let season_string = data
-                    .season_number
-                    .map_or(String::new(), |s| format!("Season {s}"));
-                let episode_string = data
-                    .episode_number
-                    .map_or(String::new(), |e| format!("Episode {e}"));
+                prefix_text = format!("{}{quality_suffix}", locale.text("new_episode_available"));
+                let season_string = data.season_number.map_or(String::new(), |s| {
+                    locale.format("season", &[("season", &s)])
+                });
+                let episode_string = data.episode_number.map_or(String::new(), |e| {
+                    locale.format("episode", &[("episode", &e)])
+                });
                 title = format!(
                     "{} - {} {}",
                     media_request.media.title, season_string, episode_string
                 );
-                message = String::new();
+
+                let episode = data.episode_metadata.clone().unwrap_or_default();
+                if let Some(url) = episode
+                    .still_url
+                    .as_ref()
+                    .or(media_request.image_url.as_ref())
+                {
+                    params.push(("photo", url.to_string()));
+                    is_photo = true;
+                }
+                message = [
+                    episode.title.as_ref().map(|v| format!("<i>{v}</i>")),
+                    episode
+                        .air_date
+                        .as_ref()
+                        .map(|v| format!("<b>{}:</b> {v}", locale.text("label_air_date"))),
+                    episode
+                        .overview_text(
+                            &telegram.episode_overview,
+                            ("<tg-spoiler>", "</tg-spoiler>"),
+                        )
+                        .map(|v| format!("\n{v}")),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<String>>()
+                .join("\n");
+            }
+            NotificationType::EpisodeDigestAvailable => {
+                if let Some(url) = &media_request.image_url {
+                    params.push(("photo", url.to_string()));
+                    is_photo = true;
+                }
+                prefix_text = format!("{}{quality_suffix}", locale.text("new_episodes_available"));
+                let episodes_string =
+                    episode_ranges_string(data.episodes.as_deref().unwrap_or_default());
+                title = media_request.media.title.clone();
+                message = match data.season_number {
+                    Some(season) => locale.format(
+                        "episodes_of_season_available",
+                        &[("episodes", &episodes_string), ("season", &season)],
+                    ),
+                    None => locale.format("episodes_available", &[("episodes", &episodes_string)]),
+                };
+            }
+            NotificationType::AlbumAvailable => {
+                if let Some(url) = &media_request.image_url {
+                    params.push(("photo", url.to_string()));
+                    is_photo = true;
+                }
+                prefix_text = locale.text("new_album_available");
+                let album_metadata = data.album_metadata.as_ref();
+                title = match album_metadata {
+                    Some(metadata) => {
+                        format!("{} - {}", metadata.artist, media_request.media.title)
+                    }
+                    None => media_request.media.title.clone(),
+                };
+                let metadata_lines = album_metadata
+                    .map(|metadata| {
+                        [
+                            metadata
+                                .album_type
+                                .as_ref()
+                                .map(|v| format!("<b>{}:</b> {v}", locale.text("label_type"))),
+                            metadata
+                                .release_date
+                                .as_ref()
+                                .map(|v| format!("<b>{}:</b> {v}", locale.text("label_released"))),
+                            Some(metadata.track_count)
+                                .filter(|count| *count > 0)
+                                .map(|v| format!("<b>{}:</b> {v}", locale.text("label_tracks"))),
+                        ]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                    })
+                    .unwrap_or_default();
+                message = format!("{}\n\n{}", media_request.media.overview, metadata_lines)
+                    .trim()
+                    .to_string();
+            }
+            NotificationType::BookAvailable => {
+                if let Some(url) = &media_request.image_url {
+                    params.push(("photo", url.to_string()));
+                    is_photo = true;
+                }
+                let book_metadata = data.book_metadata.as_ref();
+                prefix_text = match book_metadata.map(|metadata| &metadata.format) {
+                    Some(BookFormat::Audiobook) => locale.text("new_audiobook_available"),
+                    _ => locale.text("new_book_available"),
+                };
+                title = match book_metadata {
+                    Some(metadata) => {
+                        format!("{} - {}", media_request.media.title, metadata.author)
+                    }
+                    None => media_request.media.title.clone(),
+                };
+                let metadata_lines = book_metadata
+                    .map(|metadata| {
+                        [
+                            Some(format!(
+                                "<b>{}:</b> {}",
+                                locale.text("label_format"),
+                                locale.text(metadata.format.text_key())
+                            )),
+                            metadata
+                                .release_date
+                                .as_ref()
+                                .map(|v| format!("<b>{}:</b> {v}", locale.text("label_released"))),
+                        ]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                    })
+                    .unwrap_or_default();
+                message = format!("{}\n\n{}", media_request.media.overview, metadata_lines)
+                    .trim()
+                    .to_string();
+            }
+            NotificationType::RequestStatusChanged(status) => {
+                if let Some(url) = &media_request.image_url {
+                    params.push(("photo", url.to_string()));
+                    is_photo = true;
+                }
+                prefix_text = format!("{}{quality_suffix}", locale.text(&status.header_key()));
+                title = media_request.media.title.clone();
+                let seasons_line = data.seasons.as_ref().map(|seasons| {
+                    let seasons_joined = seasons
+                        .iter()
+                        .map(|n| n.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ");
+                    format!("<b>{}:</b> {seasons_joined}", locale.text("label_seasons"))
+                });
+                message = [
+                    Some(media_request.media.overview.clone()),
+                    Some(String::new()),
+                    Some(format!(
+                        "<b>{}:</b> {}",
+                        locale.text("label_request_status"),
+                        locale.text(&status.text_key())
+                    )),
+                    seasons_line,
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<String>>()
+                .join("\n")
+                .trim()
+                .to_string();
             }
         };
 
-        form = form.text(
+        let [prefix_text, title, message] =
+            self.apply_templates(Destination::Telegram, data, [prefix_text, title, message]);
+
+        let message = match &data.play_url {
+            Some(url) => format!(
+                "{message}\n\n<a href=\"{url}\">{}</a>",
+                locale.text("play_now")
+            ),
+            None => message,
+        };
+
+        params.push((
             if is_photo { "caption" } else { "text" },
             format!("<b>{prefix_text}</b>\n\n<b>{title}</b>\n\n{message}")
                 .trim()
                 .to_string(),
-        );
-
-        self.send_telegram_request(form, is_photo)
-            .await
-            .map_err(|e| anyhow!("Failed sending telegram request: {e}"))?;
+        ));
 
-        Ok(())
+        Some(OutboundMessage::Telegram {
+            params: params
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect::<BTreeMap<String, String>>(),
+            is_photo,
+        })
     }
 
-    async fn send_telegram_request(
+    fn build_discord_message(
         &self,
-        form: reqwest::multipart::Form,
-        is_photo: bool,
-    ) -> Result<()> {
-        let bot_token = match self.telegram {
-            Some(ref config) => &config.bot_token,
-            None => return Ok(()),
-        };
-
-        // Create a reqwest client
-        let client = reqwest::Client::new();
-
-        // Send the POST request to the Telegram Bot API
-        let url = if is_photo {
-            format!("https://api.telegram.org/bot{}/sendPhoto", bot_token)
-        } else {
-            format!("https://api.telegram.org/bot{}/sendMessage", bot_token)
-        };
-        let response = client
-            .post(&url)
-            .multipart(form)
-            .send()
-            .await
-            .context("POST failed")?;
-
-        // Check if the request was successful
-        if !response.status().is_success() {
-            error!(
-                "Failed to send photo: {} {:?}",
-                response.status(),
-                response.text().await
-            );
-        }
-
-        Ok(())
-    }
-
-    async fn send_discord_notification(&self, data: &NotificationData) -> Result<()> {
-        let discord = match self.discord {
-            Some(ref config) => config,
-            None => return Ok(()),
-        };
+        data: &NotificationData,
+        poster_color: Option<u64>,
+    ) -> Option<OutboundMessage> {
+        let discord = self.discord.as_ref()?;
+        let locale = self.locale(Destination::Discord, data);
 
         let media_request = &data.media_request;
 
-        let color = EmbedColors::Green;
         let mut fields = Vec::new();
 
         fields.push(json!({
-            "name": "Requested By",
+            "name": locale.text("label_requested_by"),
             "value": media_request.requested_by.display_name,
             "inline": true,
         }));
 
-        // let status;
-        // (color, status) = match payload.notification_type {
-        //     seerr::NotificationType::MediaPending => (EmbedColors::Orange, "Pending Approval"),
-        //     seerr::NotificationType::MediaApproved
-        //     | seerr::NotificationType::MediaAutoApproved => {
-        //         (EmbedColors::Purple, "Processing")
-        //     }
-        //     seerr::NotificationType::MediaAvailable => (EmbedColors::Green, "Available"),
-        //     seerr::NotificationType::MediaDeclined => (EmbedColors::Red, "Declined"),
-        //     seerr::NotificationType::MediaFailed => (EmbedColors::Red, "Failed"),
-        //     _ => (color, ""),
-        // };
-
-        // fields.push(json!({
-        //     "name": "Request Status",
-        //     "value": "Available",
-        //     "inline": true,
-        // }));
-
         // if let Some(comment) = &payload.comment {
         //     fields.push(json!({
         //         "name": format!("Comment from {}", comment.commented_by_username),
@@ -286,6 +1040,12 @@ impl NotificationController {
         //     };
         // }
 
+        let quality_suffix = if media_request.is_4k {
+            locale.text("in_4k")
+        } else {
+            String::new()
+        };
+
         let pre_title;
         let title;
         let description;
@@ -298,83 +1058,267 @@ impl NotificationController {
 
         match data.r#type {
             NotificationType::MediaAvailable => {
-                pre_title = "New Content Available".to_string();
+                pre_title = format!("{}{quality_suffix}", locale.text("new_content_available"));
                 title = media_request.media.title.clone();
                 description = media_request.media.overview.clone();
 
-                if media_request.r#type == MediaType::TV && data.seasons.is_some() {
-                    if let Some(seasons) = &data.seasons {
-                        let seasons_joined = seasons
-                            .iter()
-                            .map(|n| n.to_string())
-                            .collect::<Vec<String>>()
-                            .join(", ");
+                if let Some(metadata) = &data.movie_metadata {
+                    if let Some(runtime) = metadata.runtime_string() {
                         fields.push(json!({
-                          "name": "Seasons",
-                          "value": seasons_joined,
+                          "name": locale.text("label_runtime"),
+                          "value": runtime,
                           "inline": true,
                         }));
                     }
-                }
+                    if let Some(certification) = &metadata.certification {
+                        fields.push(json!({
+                          "name": locale.text("label_certification"),
+                          "value": certification,
+                          "inline": true,
+                        }));
+                    }
+                    if let Some(rating) = metadata.rating_string() {
+                        fields.push(json!({
+                          "name": locale.text("label_rating"),
+                          "value": rating,
+                          "inline": true,
+                        }));
+                    }
+                }
+
+                if media_request.r#type == MediaType::TV && data.seasons.is_some() {
+                    if let Some(seasons) = &data.seasons {
+                        let seasons_joined = seasons
+                            .iter()
+                            .map(|n| n.to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ");
+                        fields.push(json!({
+                          "name": locale.text("label_seasons"),
+                          "value": seasons_joined,
+                          "inline": true,
+                        }));
+                    }
+                }
             }
             NotificationType::OngoingSeasonAvailable => {
-                pre_title = "New Content Available".to_string();
+                pre_title = format!("{}{quality_suffix}", locale.text("new_content_available"));
                 title = media_request.media.title.clone();
                 description = media_request.media.overview.clone();
 
                 let season_string = data.season_number.map_or(String::new(), |s| format!("{s}"));
-                let episode_string = data
-                    .episode_number
-                    .map_or(String::new(), |e| format!("1 to {e}"));
+                let episode_string = data.episode_number.map_or(String::new(), |e| {
+                    locale.format("episode_range_short", &[("episode", &e)])
+                });
 
                 fields.push(json!({
-                  "name": "Season",
+                  "name": locale.text("label_season"),
                   "value": season_string,
                   "inline": true,
                 }));
                 fields.push(json!({
-                  "name": "Episode",
+                  "name": locale.text("label_episode"),
                   "value": episode_string,
                   "inline": true,
                 }))
             }
             NotificationType::OngoingEpisodeAvailable => {
-                pre_title = "New Episode Now Available".to_string();
-                let season_string = data
-                    .season_number
-                    .map_or(String::new(), |s| format!("Season {s}"));
-                let episode_string = data
-                    .episode_number
-                    .map_or(String::new(), |e| format!("Episode {e}"));
+                pre_title = format!(
+                    "{}{quality_suffix}",
+                    locale.text("new_episode_now_available")
+                );
+                let season_string = data.season_number.map_or(String::new(), |s| {
+                    locale.format("season", &[("season", &s)])
+                });
+                let episode_string = data.episode_number.map_or(String::new(), |e| {
+                    locale.format("episode", &[("episode", &e)])
+                });
 
                 title = media_request.media.title.clone();
-                description = String::new();
+
+                let episode = data.episode_metadata.clone().unwrap_or_default();
+                description = [
+                    episode.title.as_ref().map(|v| format!("**{v}**")),
+                    episode.overview_text(&discord.episode_overview, ("||", "||")),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<String>>()
+                .join("\n\n");
 
                 fields.push(json!({
-                  "name": "Season",
+                  "name": locale.text("label_season"),
                   "value": season_string,
                   "inline": true,
                 }));
                 fields.push(json!({
-                  "name": "Episode",
+                  "name": locale.text("label_episode"),
                   "value": episode_string,
                   "inline": true,
-                }))
+                }));
+                if let Some(air_date) = &episode.air_date {
+                    fields.push(json!({
+                      "name": locale.text("label_air_date"),
+                      "value": air_date,
+                      "inline": true,
+                    }));
+                }
+            }
+            NotificationType::EpisodeDigestAvailable => {
+                pre_title = format!(
+                    "{}{quality_suffix}",
+                    locale.text("new_episodes_now_available")
+                );
+                title = media_request.media.title.clone();
+
+                let episodes_string =
+                    episode_ranges_string(data.episodes.as_deref().unwrap_or_default());
+                description = match data.season_number {
+                    Some(season) => locale.format(
+                        "episodes_of_season_available",
+                        &[("episodes", &episodes_string), ("season", &season)],
+                    ),
+                    None => locale.format("episodes_available", &[("episodes", &episodes_string)]),
+                };
+
+                fields.push(json!({
+                  "name": locale.text("label_season"),
+                  "value": data.season_number.map_or(String::new(), |s| format!("{s}")),
+                  "inline": true,
+                }));
+                fields.push(json!({
+                  "name": locale.text("label_episodes"),
+                  "value": episodes_string,
+                  "inline": true,
+                }));
+            }
+            NotificationType::AlbumAvailable => {
+                pre_title = locale.text("new_album_available");
+                title = media_request.media.title.clone();
+                description = media_request.media.overview.clone();
+
+                if let Some(metadata) = &data.album_metadata {
+                    fields.push(json!({
+                      "name": locale.text("label_artist"),
+                      "value": metadata.artist,
+                      "inline": true,
+                    }));
+                    if let Some(album_type) = &metadata.album_type {
+                        fields.push(json!({
+                          "name": locale.text("label_type"),
+                          "value": album_type,
+                          "inline": true,
+                        }));
+                    }
+                    if let Some(release_date) = &metadata.release_date {
+                        fields.push(json!({
+                          "name": locale.text("label_released"),
+                          "value": release_date,
+                          "inline": true,
+                        }));
+                    }
+                    if metadata.track_count > 0 {
+                        fields.push(json!({
+                          "name": locale.text("label_tracks"),
+                          "value": metadata.track_count,
+                          "inline": true,
+                        }));
+                    }
+                }
+            }
+            NotificationType::BookAvailable => {
+                title = media_request.media.title.clone();
+                description = media_request.media.overview.clone();
+
+                match &data.book_metadata {
+                    Some(metadata) => {
+                        pre_title = match metadata.format {
+                            BookFormat::Ebook => locale.text("new_book_available"),
+                            BookFormat::Audiobook => locale.text("new_audiobook_available"),
+                        };
+                        fields.push(json!({
+                          "name": locale.text("label_author"),
+                          "value": metadata.author,
+                          "inline": true,
+                        }));
+                        fields.push(json!({
+                          "name": locale.text("label_format"),
+                          "value": locale.text(metadata.format.text_key()),
+                          "inline": true,
+                        }));
+                        if let Some(release_date) = &metadata.release_date {
+                            fields.push(json!({
+                              "name": locale.text("label_released"),
+                              "value": release_date,
+                              "inline": true,
+                            }));
+                        }
+                    }
+                    None => pre_title = locale.text("new_book_available"),
+                }
+            }
+            NotificationType::RequestStatusChanged(status) => {
+                pre_title = format!("{}{quality_suffix}", locale.text(&status.header_key()));
+                title = media_request.media.title.clone();
+                description = media_request.media.overview.clone();
+
+                fields.push(json!({
+                  "name": locale.text("label_request_status"),
+                  "value": locale.text(&status.text_key()),
+                  "inline": true,
+                }));
+                if let Some(seasons) = &data.seasons {
+                    let seasons_joined = seasons
+                        .iter()
+                        .map(|n| n.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ");
+                    fields.push(json!({
+                      "name": locale.text("label_seasons"),
+                      "value": seasons_joined,
+                      "inline": true,
+                    }));
+                }
             }
         }
 
+        let [pre_title, title, description] =
+            self.apply_templates(Destination::Discord, data, [pre_title, title, description]);
+
+        // Explicit colors first, then the lifecycle status and the poster
+        let status_color = match data.r#type {
+            NotificationType::RequestStatusChanged(status) => Some(status.color().value()),
+            _ => None,
+        };
+        let color = self
+            .discord_colors
+            .for_type(data.r#type.name())
+            .or(status_color)
+            .or(poster_color)
+            .or(self.discord_colors.fallback())
+            .unwrap_or(EmbedColors::Green.value());
+
+        if let Some(url) = &data.play_url {
+            fields.push(json!({
+              "name": locale.text("label_watch"),
+              "value": format!("[{}]({url})", locale.text("play_now")),
+              "inline": false,
+            }));
+        }
+
         let content = match &media_request.requested_by.discord_id {
             Some(val) => format!("<@{val}>"),
             None => String::new(),
         };
 
-        let message = json!({
+        let mut message = json!({
             "username": discord.username,
             "avatar_url": discord.avatar_url,
             "content": content,
             "embeds": [
                 {
                     "title": title,
+                    "url": data.play_url.as_ref().map(|url| url.to_string()),
                     "description": description,
                     "color": color,
                     "timestamp": OffsetDateTime::now_utc().format(&Rfc3339).unwrap_or_default(),
@@ -390,55 +1334,225 @@ impl NotificationController {
             ]
         });
 
-        if let Err(err) = self.send_discord_request(message).await {
-            error!("Failed sending Discord message: {err}");
+        let still_url = data
+            .episode_metadata
+            .as_ref()
+            .and_then(|episode| episode.still_url.as_ref());
+        if let Some(url) = still_url {
+            message["embeds"][0]["image"] = json!({ "url": url.to_string() });
         }
 
-        Ok(())
+        Some(OutboundMessage::Discord { message })
     }
+}
 
-    async fn send_discord_request(&self, data: Value) -> Result<()> {
-        let webhook_url = match self.discord {
-            Some(ref config) => &config.webhook_url,
-            None => return Ok(()),
-        };
+struct SonarrInstance {
+    api: sonarr::apis::ApiClient,
+    name: Option<String>,
+    application_url: Option<String>,
+    is_4k: bool,
+}
 
-        // Create a reqwest client
-        let client = reqwest::Client::new();
+impl SonarrInstance {
+    fn matches_event(&self, instance_name: &str, application_url: &str) -> bool {
+        instance_matches_event(
+            self.name.as_deref(),
+            self.application_url.as_deref(),
+            instance_name,
+            application_url,
+        )
+    }
+}
 
-        // Send the message via the Webhook URL using a POST request
-        let response: reqwest::Response = client
-            .post(webhook_url)
-            .header("Content-Type", "application/json")
-            .json(&data)
-            .send()
-            .await
-            .context("POST message failed")?;
-
-        // Check if the request was successful
-        if !response.status().is_success() {
-            return Err(anyhow!(
-                "Response status: {:?} {:?}",
-                response.status(),
-                response.json::<Value>().await
-            ));
-        }
+struct RadarrInstance {
+    api: radarr::apis::ApiClient,
+    name: Option<String>,
+    application_url: Option<String>,
+    is_4k: bool,
+}
 
-        Ok(())
+impl RadarrInstance {
+    fn matches_event(&self, instance_name: &str, application_url: &str) -> bool {
+        instance_matches_event(
+            self.name.as_deref(),
+            self.application_url.as_deref(),
+            instance_name,
+            application_url,
+        )
+    }
+}
+
+/// Names of the instances configured in Seerr, the repeated ones get their Seerr id appended so
+/// webhook paths and events can still be routed to them
+///
+/// The names of the configuration file are checked when it is loaded, but the ones of Seerr can
+/// only be fixed in Seerr.
+fn seerr_instance_names(service: &str, instances: &[(&str, Option<f64>)]) -> Vec<String> {
+    let normalize = |name: &str| name.trim().to_lowercase();
+    instances
+        .iter()
+        .map(|(name, id)| {
+            let count = instances
+                .iter()
+                .filter(|(other, _)| normalize(other) == normalize(name))
+                .count();
+            match id {
+                Some(id) if count > 1 => {
+                    let unique_name = format!("{name}-{id}");
+                    warn!(
+                        "Several {service} instances of Seerr are named '{name}', using '{unique_name}' for the one with id {id}"
+                    );
+                    unique_name
+                }
+                _ => name.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Checks if the `instanceName` or `applicationUrl` sent by an *arr webhook refers to the
+/// configured instance
+fn instance_matches_event(
+    name: Option<&str>,
+    base_url: Option<&str>,
+    instance_name: &str,
+    application_url: &str,
+) -> bool {
+    let normalize_url = |url: &str| url.trim().trim_end_matches('/').to_lowercase();
+
+    let name_matches = name.is_some_and(|name| {
+        !instance_name.is_empty() && name.trim().eq_ignore_ascii_case(instance_name.trim())
+    });
+    let url_matches = base_url.is_some_and(|url| {
+        !application_url.is_empty() && normalize_url(url) == normalize_url(application_url)
+    });
+
+    name_matches || url_matches
+}
+
+struct LidarrInstance {
+    api: lidarr::apis::ApiClient,
+    name: Option<String>,
+    application_url: Option<String>,
+}
+
+impl LidarrInstance {
+    fn matches_event(&self, instance_name: &str, application_url: &str) -> bool {
+        instance_matches_event(
+            self.name.as_deref(),
+            self.application_url.as_deref(),
+            instance_name,
+            application_url,
+        )
     }
 }
 
+/// Notification held until the media appears in the media server library
+struct PendingPlayback {
+    data: NotificationData,
+    query: MediaItemQuery,
+    queued_at: Instant,
+}
+
+/// Builds the media server query for the notifications of movies and shows
+fn playback_query(data: &NotificationData) -> Option<MediaItemQuery> {
+    let media_request = &data.media_request;
+    let is_show = match media_request.r#type {
+        MediaType::MOVIE => false,
+        MediaType::TV => true,
+        _ => return None,
+    };
+
+    let (season_number, episode_number) = match data.r#type {
+        NotificationType::MediaAvailable => (
+            data.seasons
+                .as_ref()
+                .and_then(|seasons| seasons.iter().max().copied()),
+            None,
+        ),
+        NotificationType::OngoingSeasonAvailable | NotificationType::OngoingEpisodeAvailable => {
+            (data.season_number, data.episode_number)
+        }
+        _ => return None,
+    };
+
+    Some(MediaItemQuery {
+        tmdb_id: media_request.media.tmdb_id,
+        tvdb_id: media_request.media.tvdb_id,
+        is_show,
+        season_number: season_number.filter(|_| is_show),
+        episode_number: episode_number.filter(|_| is_show),
+    })
+}
+
+/// Time between two full syncs of the requests, which drop the ones deleted from Seerr
+const FULL_SYNC_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Progress of the sync of the requests with Seerr
+#[derive(Default)]
+struct RequestSync {
+    /// Latest `updatedAt` of the synced requests, the next sync only fetches the newer ones
+    updated_at: Option<OffsetDateTime>,
+    full_sync_at: Option<Instant>,
+}
+
+/// State kept across configuration reloads, shared by the workers processing the events
+#[derive(Default)]
+struct HandlerState {
+    requested: RwLock<Vec<MediaRequest>>,
+    pending_playback: Mutex<Vec<PendingPlayback>>,
+    sync: Mutex<RequestSync>,
+    /// Changes made by the events while a full sync fetches the requests replacing the stored
+    /// ones
+    sync_journal: Mutex<Option<Vec<RequestChange>>>,
+}
+
 struct RequestHandler {
     seerr_api: seerr::apis::ApiClient,
-    sonarr_apis: Vec<sonarr::apis::ApiClient>,
-    // radarr_apis: Vec<radarr::apis::ApiClient>,
-    requested: Vec<MediaRequest>,
-    // users: HashMap<i32, Arc<SeerrUser>>,
+    sync_interval: Duration,
+    sonarr_apis: Vec<SonarrInstance>,
+    radarr_apis: Vec<RadarrInstance>,
+    lidarr_apis: Vec<LidarrInstance>,
+    music_requesters: Vec<MusicRequesterConfig>,
+    cache: Arc<MetadataCache>,
     notifier: NotificationController,
+    media_server: Option<MediaServerClient>,
+    /// Seerr could not be reached to retrieve the *arr instances, the handler is created again
+    /// on the next health check
+    is_missing_instances: bool,
+    state: Arc<HandlerState>,
+    health: Arc<UpstreamHealth>,
 }
 
 impl RequestHandler {
-    async fn new(mut app_config: AppConfig) -> Result<Self> {
+    async fn new(
+        app_config: AppConfig,
+        templates: MessageTemplates,
+        queue: Arc<NotificationQueue>,
+        health: Arc<UpstreamHealth>,
+        cache: Arc<MetadataCache>,
+    ) -> Result<Self> {
+        let mut instance = Self::connect(app_config, templates, queue, health, cache).await?;
+
+        let result = instance.fetch_requests().await;
+        instance.health.record(health::SEERR, &result);
+        result.map_err(|e| anyhow!("Could not fetch initial requests: {e}"))?;
+
+        Ok(instance)
+    }
+
+    /// Creates the clients of the configuration, without fetching the requests
+    ///
+    /// The upstreams that cannot be reached are only marked as unhealthy, the media server and
+    /// the instances configured in Seerr are retried on the next health check.
+    async fn connect(
+        mut app_config: AppConfig,
+        templates: MessageTemplates,
+        queue: Arc<NotificationQueue>,
+        health: Arc<UpstreamHealth>,
+        cache: Arc<MetadataCache>,
+    ) -> Result<Self> {
+        let sync_interval = Duration::from_secs(app_config.seerr.sync_interval * 60);
         let config = seerr::apis::configuration::Configuration {
             base_path: app_config.seerr.url + "/api/v1",
             api_key: Some(seerr::apis::configuration::ApiKey {
@@ -458,35 +1572,78 @@ impl RequestHandler {
             )
         };
 
+        // The instances configured in Seerr are retrieved again on the next health check if it
+        // cannot be reached
+        let mut is_missing_instances = false;
         let sonarr_settings = if let Some(config) = app_config.sonarr.take() {
             config
         } else {
-            seerr_api
-                .settings_api()
-                .settings_sonarr_get()
-                .await
-                .map_err(|e| anyhow!("Could not retrieve Sonarr settings {e}"))?
-                .into_iter()
-                .map(|s| SonarrConfig {
-                    url: convert_to_url(s.use_ssl, s.hostname, s.port),
-                    api_key: s.api_key,
-                })
-                .collect()
+            let settings = seerr_api.settings_api().settings_sonarr_get().await;
+            health.record(health::SEERR, &settings);
+            match settings {
+                Ok(settings) => {
+                    let names = seerr_instance_names(
+                        "Sonarr",
+                        &settings
+                            .iter()
+                            .map(|s| (s.name.as_str(), s.id))
+                            .collect::<Vec<_>>(),
+                    );
+                    settings
+                        .into_iter()
+                        .zip(names)
+                        .map(|(s, name)| SonarrConfig {
+                            name: Some(name),
+                            url: convert_to_url(s.use_ssl, s.hostname, s.port),
+                            application_url: s.external_url.filter(|url| !url.is_empty()),
+                            api_key: s.api_key,
+                            is_4k: s.is4k,
+                        })
+                        .collect()
+                }
+                Err(e) => {
+                    warn!(
+                        "Could not retrieve Sonarr settings, retrying on the next health check: {e}"
+                    );
+                    is_missing_instances = true;
+                    Vec::new()
+                }
+            }
         };
         let radarr_settings = if let Some(config) = app_config.radarr.take() {
             config
         } else {
-            seerr_api
-                .settings_api()
-                .settings_radarr_get()
-                .await
-                .map_err(|e| anyhow!("Could not retrieve Radarr settings {e}"))?
-                .into_iter()
-                .map(|s| RadarrConfig {
-                    url: convert_to_url(s.use_ssl, s.hostname, s.port),
-                    api_key: s.api_key,
-                })
-                .collect()
+            let settings = seerr_api.settings_api().settings_radarr_get().await;
+            health.record(health::SEERR, &settings);
+            match settings {
+                Ok(settings) => {
+                    let names = seerr_instance_names(
+                        "Radarr",
+                        &settings
+                            .iter()
+                            .map(|s| (s.name.as_str(), s.id))
+                            .collect::<Vec<_>>(),
+                    );
+                    settings
+                        .into_iter()
+                        .zip(names)
+                        .map(|(s, name)| RadarrConfig {
+                            name: Some(name),
+                            url: convert_to_url(s.use_ssl, s.hostname, s.port),
+                            application_url: s.external_url.filter(|url| !url.is_empty()),
+                            api_key: s.api_key,
+                            is_4k: s.is4k,
+                        })
+                        .collect()
+                }
+                Err(e) => {
+                    warn!(
+                        "Could not retrieve Radarr settings, retrying on the next health check: {e}"
+                    );
+                    is_missing_instances = true;
+                    Vec::new()
+                }
+            }
         };
 
         let sonarr_apis = sonarr_settings
@@ -500,9 +1657,14 @@ impl RequestHandler {
                     }),
                     ..Default::default()
                 };
-                sonarr::apis::ApiClient::new(config.into())
+                SonarrInstance {
+                    api: sonarr::apis::ApiClient::new(config.into()),
+                    name: settings.name,
+                    application_url: settings.application_url,
+                    is_4k: settings.is_4k,
+                }
             })
-            .collect::<Vec<sonarr::apis::ApiClient>>();
+            .collect::<Vec<SonarrInstance>>();
 
         let radarr_apis = radarr_settings
             .into_iter()
@@ -515,153 +1677,441 @@ impl RequestHandler {
                     }),
                     ..Default::default()
                 };
-                radarr::apis::ApiClient::new(config.into())
+                RadarrInstance {
+                    api: radarr::apis::ApiClient::new(config.into()),
+                    name: settings.name,
+                    application_url: settings.application_url,
+                    is_4k: settings.is_4k,
+                }
+            })
+            .collect::<Vec<RadarrInstance>>();
+
+        let lidarr_apis = app_config
+            .lidarr
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|settings| {
+                let config = lidarr::apis::configuration::Configuration {
+                    base_path: settings.url,
+                    api_key: Some(lidarr::apis::configuration::ApiKey {
+                        prefix: None,
+                        key: settings.api_key,
+                    }),
+                    ..Default::default()
+                };
+                LidarrInstance {
+                    api: lidarr::apis::ApiClient::new(config.into()),
+                    name: settings.name,
+                    application_url: settings.application_url,
+                }
             })
-            .collect::<Vec<radarr::apis::ApiClient>>();
+            .collect::<Vec<LidarrInstance>>();
 
         debug!("Sonarr instances: {}", sonarr_apis.len());
         debug!("Radarr instances: {}", radarr_apis.len());
+        debug!("Lidarr instances: {}", lidarr_apis.len());
 
-        for instance in &sonarr_apis {
-            instance
-                .api_info_api()
-                .api_get()
-                .await
-                .map_err(|e| anyhow!("Could not retrieve Sonarr API info {e}"))?;
-        }
-
-        for instance in &radarr_apis {
-            instance
-                .api_info_api()
-                .api_get()
-                .await
-                .map_err(|e| anyhow!("Could not retrieve Radarr API info {e}"))?;
+        let media_server = app_config.media_server.take().map(MediaServerClient::new);
+        if let Some(media_server) = &media_server {
+            let result = media_server.check().await;
+            if let Err(e) = &result {
+                warn!("{e}, retrying on the next health check");
+            }
+            health.record(health::MEDIA_SERVER, &result);
         }
 
-        let mut instance = Self {
+        let handler = Self {
             seerr_api,
+            sync_interval,
             sonarr_apis,
-            // radarr_apis,
-            requested: Vec::new(),
-            // users: HashMap::new(),
-            notifier: NotificationController::new(app_config.discord, app_config.telegram),
+            radarr_apis,
+            lidarr_apis,
+            music_requesters: app_config.music_requesters.unwrap_or_default(),
+            notifier: NotificationController::new(
+                app_config.discord,
+                app_config.telegram,
+                templates,
+                queue,
+                cache.clone(),
+            ),
+            cache,
+            media_server,
+            is_missing_instances,
+            state: Arc::default(),
+            health,
         };
+        handler.check_instances().await;
+        Ok(handler)
+    }
 
-        instance
-            .fetch_requests()
-            .await
-            .map_err(|e| anyhow!("Could not fetch initial requests: {e}"))?;
+    /// Checks the connection to the *arr instances, the failing ones are logged and marked as
+    /// unhealthy
+    async fn check_instances(&self) {
+        let mut names = vec![health::SEERR.to_string()];
+        if self.media_server.is_some() {
+            names.push(health::MEDIA_SERVER.to_string());
+        }
 
-        Ok(instance)
-    }
+        for instance in &self.sonarr_apis {
+            let name = health::instance_name("sonarr", instance.name.as_deref());
+            let result = instance.api.api_info_api().api_get().await;
+            if let Err(e) = &result {
+                warn!("Could not retrieve Sonarr API info of {name}: {e}");
+            }
+            self.health.record(&name, &result);
+            names.push(name);
+        }
 
-    async fn process_seerr(&mut self, event: SeerrEvent) -> Result<()> {
-        if event.notification_type == webhooks::seerr::NotificationType::MediaApproved
-            || event.notification_type == webhooks::seerr::NotificationType::MediaAutoApproved
-        {
-            let request_id = match event.request {
-                Some(request) => request.request_id,
-                None => return Ok(()),
-            };
+        for instance in &self.radarr_apis {
+            let name = health::instance_name("radarr", instance.name.as_deref());
+            let result = instance.api.api_info_api().api_get().await;
+            if let Err(e) = &result {
+                warn!("Could not retrieve Radarr API info of {name}: {e}");
+            }
+            self.health.record(&name, &result);
+            names.push(name);
+        }
 
-            if let Ok(media_request) = self
-                .seerr_api
-                .request_api()
-                .request_request_id_get(
-                    RequestRequestIdGetParams::builder()
-                        .request_id(request_id)
-                        .build(),
-                )
-                .await
-            {
-                self.process_request(media_request)
-                    .await
-                    .map_err(|e| anyhow!("Failed processing Jellyfin request: {e}"))?;
+        for instance in &self.lidarr_apis {
+            let name = health::instance_name("lidarr", instance.name.as_deref());
+            let result = instance.api.api_info_api().api_get().await;
+            if let Err(e) = &result {
+                warn!("Could not retrieve Lidarr API info of {name}: {e}");
             }
+            self.health.record(&name, &result);
+            names.push(name);
         }
 
-        Ok(())
+        self.health.retain(&names);
     }
 
-    async fn process_sonarr(&mut self, event: SonarrEvent) -> Result<()> {
-        let download_event = match event {
-            SonarrEvent::Download(event) => event,
-            _ => return Ok(()),
-        };
-
-        let is_import_completed_event = download_event.episode_files.is_some();
-        let is_upgrade = download_event.is_upgrade;
-        if is_import_completed_event || is_upgrade {
-            debug!("Is import complete or upgrade, skipping");
-            return Ok(());
+    /// Checks every upstream, used to notice when the failing ones are back
+    async fn check_upstreams(&self) {
+        let result = self.seerr_api.request_api().request_count_get().await;
+        self.health.record(health::SEERR, &result);
+        if let Some(media_server) = &self.media_server {
+            let result = media_server.check().await;
+            self.health.record(health::MEDIA_SERVER, &result);
         }
+        self.check_instances().await;
+    }
 
-        let requested_show = self
-            .get_tv_request(
-                download_event.series.tmdb_id,
-                Some(download_event.series.tvdb_id),
-            )
-            .cloned()
-            .ok_or(anyhow!(
-                "Could not find requested show with tmdb id {}",
-                download_event.series.tmdb_id
-            ))?;
+    /// Creates a handler with the clients and notifiers of the new configuration, sharing the
+    /// requests, held notifications and digests with this one so the events still processed by
+    /// it are not lost
+    async fn reload(&self, app_config: AppConfig, templates: MessageTemplates) -> Result<Self> {
+        let queue = self.notifier.queue.clone();
+        let mut handler = Self::connect(
+            app_config,
+            templates,
+            queue,
+            self.health.clone(),
+            self.cache.clone(),
+        )
+        .await?;
+
+        handler.state = self.state.clone();
+        handler.notifier.digests = self.notifier.digests.clone();
+        Ok(handler)
+    }
 
-        let requested_seasons = requested_show.seasons.as_ref().ok_or(anyhow!(
-            "Could not find seasons for requested show with tmdb id {}",
-            download_event.series.tmdb_id
-        ))?;
+    /// Sends the notification, holding the ones of movies and shows until they are playable in
+    /// the media server if one is configured
+    async fn notify(&self, mut data: NotificationData) {
+        let (Some(media_server), Some(query)) = (self.media_server.as_ref(), playback_query(&data))
+        else {
+            self.notifier.send_notification(data).await;
+            return;
+        };
 
-        let event_seasons: Vec<i32> = download_event
-            .episodes
-            .iter()
-            .map(|episode| episode.season_number)
-            .collect();
+        match media_server.find_item(&query).await {
+            Ok(Some(item_id)) => {
+                data.play_url = media_server.play_url(&item_id);
+                self.notifier.send_notification(data).await;
+                return;
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Could not search item in {}: {e}", media_server.name()),
+        }
 
-        let requested_seasons_with_event: Vec<&SeasonInfo> = requested_seasons
-            .iter()
-            .filter(|season| event_seasons.contains(&season.season_number))
-            .collect();
+        info!(
+            "Holding notification until {} is available in {}",
+            data.media_request.media.title,
+            media_server.name()
+        );
+        self.state
+            .pending_playback
+            .lock()
+            .unwrap()
+            .push(PendingPlayback {
+                data,
+                query,
+                queued_at: Instant::now(),
+            });
+    }
 
-        if requested_seasons_with_event.is_empty() {
-            debug!("No seasons to update");
-            return Ok(());
+    /// Sends the held notifications and digests right away, used when the handler stops
+    async fn flush(&self) {
+        let pending_playback = std::mem::take(&mut *self.state.pending_playback.lock().unwrap());
+        for pending in pending_playback {
+            self.notifier.send_notification(pending.data).await;
         }
+        self.notifier.flush_all_digests().await;
+    }
 
-        let sonarr_series = {
-            let mut series = None;
-            for api in &self.sonarr_apis {
-                if let Ok(res) = api
-                    .series_api()
-                    .api_v3_series_id_get(
-                        ApiV3SeriesIdGetParams::builder()
-                            .id(download_event.series.id)
-                            .build(),
-                    )
-                    .await
-                {
-                    series = Some(res);
-                    break;
-                }
+    async fn process(&self, event: WebhookEvent) -> Result<()> {
+        let source = event.source();
+        let result = match event {
+            WebhookEvent::Sonarr(event) => {
+                info!("Processing Sonarr event: {}", event.event);
+                debug!(
+                    "Received from Sonarr: {}",
+                    serde_json::to_string(&event.event).unwrap_or_default()
+                );
+                self.process_sonarr(event).await
+            }
+            WebhookEvent::Radarr(event) => {
+                info!("Processing Radarr event: {}", event);
+                debug!(
+                    "Received from Radarr: {}",
+                    serde_json::to_string(&event).unwrap_or_default()
+                );
+                self.process_radarr(event).await
+            }
+            WebhookEvent::Lidarr(event) => {
+                info!("Processing Lidarr event: {}", event);
+                debug!(
+                    "Received from Lidarr: {}",
+                    serde_json::to_string(&event).unwrap_or_default()
+                );
+                self.process_lidarr(event).await
+            }
+            WebhookEvent::Readarr(event) => {
+                info!("Processing Readarr event: {}", event);
+                debug!(
+                    "Received from Readarr: {}",
+                    serde_json::to_string(&event).unwrap_or_default()
+                );
+                self.process_readarr(event).await
+            }
+            WebhookEvent::Seerr(event) => {
+                info!("Processing Seerr event: {}", event.notification_type);
+                debug!(
+                    "Received from Seerr: {}",
+                    serde_json::to_string(&event).unwrap_or_default()
+                );
+                self.process_seerr(event).await
+            }
+            WebhookEvent::MediaServer(event) => {
+                info!("Processing media server event: {}", event);
+                debug!(
+                    "Received from media server: {}",
+                    serde_json::to_string(&event).unwrap_or_default()
+                );
+                self.process_media_server(event).await
             }
-            series.ok_or(anyhow!(
-                "Could not find Sonarr series with id {}",
-                download_event.series.id
-            ))?
         };
+        result.map_err(|e| anyhow!("Failed processing {source} event: {e}"))
+    }
 
-        let sonarr_monitored_seasons = sonarr_series
-            .seasons
-            .unwrap_or_default()
-            .context("Could not find Sonarr series seasons not present")?;
-
-        if sonarr_monitored_seasons.is_empty() {
-            return Err(anyhow!("Could not find monitored Sonarr series seasons"));
+    async fn process_media_server(&self, event: MediaServerEvent) -> Result<()> {
+        if self.state.pending_playback.lock().unwrap().is_empty() {
+            return Ok(());
         }
 
-        let mut sonarr_available_seasons: Vec<&SonarrSeasonResource> = Vec::new();
-        let mut sonarr_completed_seasons = Vec::new();
-        let mut sonarr_ongoing_seasons = Vec::new();
+        // Episodes carry their own provider ids, so every held show has to be checked
+        let is_episode = event.item_type.eq_ignore_ascii_case("episode");
+        self.check_pending_playback(|query| {
+            is_episode
+                || event.tmdb_id == Some(query.tmdb_id)
+                || event.tvdb_id.is_some_and(|id| Some(id) == query.tvdb_id)
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Sends the held notifications whose media is already in the library, the ones that waited
+    /// longer than the configured limit are sent without link
+    ///
+    /// The checked notifications are taken out of the shared list, so concurrent checks do not
+    /// send them twice.
+    async fn check_pending_playback(&self, should_check: impl Fn(&MediaItemQuery) -> bool) {
+        let Some(media_server) = self.media_server.as_ref() else {
+            return;
+        };
+
+        let pending_playback = std::mem::take(&mut *self.state.pending_playback.lock().unwrap());
+        let mut still_pending = Vec::new();
+        for mut pending in pending_playback {
+            let item_id = if should_check(&pending.query) {
+                media_server
+                    .find_item(&pending.query)
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!("Could not search item in {}: {e}", media_server.name());
+                        None
+                    })
+            } else {
+                None
+            };
+
+            if let Some(item_id) = item_id {
+                pending.data.play_url = media_server.play_url(&item_id);
+                self.notifier.send_notification(pending.data).await;
+            } else if pending.queued_at.elapsed() >= media_server.max_wait() {
+                warn!(
+                    "{} not found in {} after waiting, sending notification without link",
+                    pending.data.media_request.media.title,
+                    media_server.name()
+                );
+                self.notifier.send_notification(pending.data).await;
+            } else {
+                still_pending.push(pending);
+            }
+        }
+        self.state
+            .pending_playback
+            .lock()
+            .unwrap()
+            .extend(still_pending);
+    }
+
+    async fn process_seerr(&self, event: SeerrEvent) -> Result<()> {
+        // The details of the media include its availability, which the event may have changed
+        if let Some(media) = &event.media
+            && let Some(tmdb_id) = media.tmdb_id
+        {
+            self.cache.invalidate_media(&media.media_type, tmdb_id);
+        }
+
+        let Some(status) = RequestStatus::from_seerr(&event.notification_type) else {
+            return Ok(());
+        };
+        let is_approved = status == RequestStatus::Processing;
+        let notifies_status = self.notifier.notifies_request_status();
+        if !is_approved && !notifies_status {
+            return Ok(());
+        }
+
+        let payload = serde_json::to_value(&event).ok();
+        let request_id = match event.request {
+            Some(request) => request.request_id,
+            None => return Ok(()),
+        };
+
+        let Ok(media_request) = self
+            .seerr_api
+            .request_api()
+            .request_request_id_get(
+                RequestRequestIdGetParams::builder()
+                    .request_id(request_id)
+                    .build(),
+            )
+            .await
+        else {
+            return Ok(());
+        };
+
+        if notifies_status {
+            let (processed_request, _) = self
+                .build_media_request(&media_request)
+                .await
+                .map_err(|e| anyhow!("Failed processing request status: {e}"))?;
+            let seasons = processed_request.seasons.as_ref().map(|seasons| {
+                seasons
+                    .iter()
+                    .map(|season| season.season_number)
+                    .collect::<Vec<i32>>()
+            });
+            self.notifier
+                .send_notification(
+                    NotificationData::builder()
+                        .r#type(NotificationType::RequestStatusChanged(status))
+                        .media_request(processed_request)
+                        .maybe_seasons(seasons)
+                        .maybe_payload(payload)
+                        .build(),
+                )
+                .await;
+        }
+
+        if is_approved {
+            self.process_request(media_request)
+                .await
+                .map_err(|e| anyhow!("Failed processing Jellyfin request: {e}"))?;
+        }
+
+        Ok(())
+    }
+
+    async fn process_sonarr(&self, event: InstanceEvent<SonarrEvent>) -> Result<()> {
+        let download_event = match event.event {
+            SonarrEvent::Download(event) => event,
+            _ => return Ok(()),
+        };
+        // The details of the show include the availability of its seasons, which Seerr updates
+        // after the download
+        self.cache
+            .invalidate_media("tv", download_event.series.tmdb_id);
+
+        let is_import_completed_event = download_event.episode_files.is_some();
+        let is_upgrade = download_event.is_upgrade;
+        if is_import_completed_event || is_upgrade {
+            debug!("Is import complete or upgrade, skipping");
+            return Ok(());
+        }
+
+        let (sonarr_series, is_4k) = self
+            .find_sonarr_series(event.instance.as_deref(), &download_event)
+            .await?;
+
+        let requested_show = self
+            .get_tv_request(
+                download_event.series.tmdb_id,
+                Some(download_event.series.tvdb_id),
+                is_4k,
+            )
+            .ok_or(anyhow!(
+                "Could not find requested {}show with tmdb id {}",
+                if is_4k { "4K " } else { "" },
+                download_event.series.tmdb_id
+            ))?;
+
+        let requested_seasons = requested_show.seasons.as_ref().ok_or(anyhow!(
+            "Could not find seasons for requested show with tmdb id {}",
+            download_event.series.tmdb_id
+        ))?;
+
+        let event_seasons: Vec<i32> = download_event
+            .episodes
+            .iter()
+            .map(|episode| episode.season_number)
+            .collect();
+
+        let requested_seasons_with_event: Vec<&SeasonInfo> = requested_seasons
+            .iter()
+            .filter(|season| event_seasons.contains(&season.season_number))
+            .collect();
+
+        if requested_seasons_with_event.is_empty() {
+            debug!("No seasons to update");
+            return Ok(());
+        }
+
+        let sonarr_monitored_seasons = sonarr_series
+            .seasons
+            .unwrap_or_default()
+            .context("Could not find Sonarr series seasons not present")?;
+
+        if sonarr_monitored_seasons.is_empty() {
+            return Err(anyhow!("Could not find monitored Sonarr series seasons"));
+        }
+
+        let mut sonarr_available_seasons: Vec<&SonarrSeasonResource> = Vec::new();
+        let mut sonarr_completed_seasons = Vec::new();
+        let mut sonarr_ongoing_seasons = Vec::new();
         for season in &sonarr_monitored_seasons {
             if let Some(stats) = &season.statistics {
                 let is_season_ongoing = stats.next_airing.is_some();
@@ -703,20 +2153,20 @@ impl RequestHandler {
         if completed_requested.len() != 0 && completed_requested.len() == available_requested.len()
         {
             info!("Sending notification for request available");
-            self.notifier
-                .send_notification(
-                    NotificationData::builder()
-                        .r#type(NotificationType::MediaAvailable)
-                        .media_request(requested_show.clone())
-                        .seasons(
-                            available_requested
-                                .iter()
-                                .map(|season| season.season_number)
-                                .collect(),
-                        )
-                        .build(),
-                )
-                .await;
+            self.notify(
+                NotificationData::builder()
+                    .maybe_payload(serde_json::to_value(&download_event).ok())
+                    .r#type(NotificationType::MediaAvailable)
+                    .media_request(requested_show.clone())
+                    .seasons(
+                        available_requested
+                            .iter()
+                            .map(|season| season.season_number)
+                            .collect(),
+                    )
+                    .build(),
+            )
+            .await;
             self.remove_request(&requested_show);
             return Ok(());
         }
@@ -769,126 +2219,806 @@ impl RequestHandler {
 
         if last_episode_air_date > requested_show.created_at || last_episode_number == 1 {
             info!("Sending notification for single episode available");
-            self.notifier
-                .send_notification(
-                    NotificationData::builder()
-                        .r#type(NotificationType::OngoingEpisodeAvailable)
-                        .media_request(requested_show)
-                        .season_number(last_episode_season)
-                        .episode_number(last_episode_number)
-                        .build(),
+            let episode_metadata = self
+                .get_episode_metadata(
+                    requested_show.media.tmdb_id,
+                    &download_event.episodes,
+                    last_episode_season,
+                    last_episode_number,
                 )
                 .await;
+            self.notify(
+                NotificationData::builder()
+                    .maybe_payload(serde_json::to_value(&download_event).ok())
+                    .r#type(NotificationType::OngoingEpisodeAvailable)
+                    .media_request(requested_show)
+                    .episode_metadata(episode_metadata)
+                    .season_number(last_episode_season)
+                    .episode_number(last_episode_number)
+                    .build(),
+            )
+            .await;
         } else {
             info!("Sending notification for multiple ongoing episodes available");
-            self.notifier
-                .send_notification(
-                    NotificationData::builder()
-                        .r#type(NotificationType::OngoingSeasonAvailable)
-                        .media_request(requested_show)
-                        .season_number(last_episode_season)
-                        .episode_number(last_episode_number)
-                        .build(),
-                )
-                .await;
+            self.notify(
+                NotificationData::builder()
+                    .maybe_payload(serde_json::to_value(&download_event).ok())
+                    .r#type(NotificationType::OngoingSeasonAvailable)
+                    .media_request(requested_show)
+                    .season_number(last_episode_season)
+                    .episode_number(last_episode_number)
+                    .build(),
+            )
+            .await;
         }
 
         return Ok(());
     }
 
-    async fn process_radarr(&mut self, event: RadarrEvent) -> Result<()> {
+    async fn process_radarr(&self, event: RadarrEvent) -> Result<()> {
         let download_event = match event {
             RadarrEvent::Download(event) => event,
             _ => return Ok(()),
         };
+        self.cache
+            .invalidate_media("movie", download_event.movie.tmdb_id);
 
-        if let Some(requested_movie) = self
-            .get_movie_request(download_event.movie.tmdb_id)
-            .cloned()
-        {
-            self.notifier
-                .send_notification(
-                    NotificationData::builder()
-                        .r#type(NotificationType::MediaAvailable)
-                        .media_request(requested_movie.clone())
+        if download_event.is_upgrade {
+            debug!("Is upgrade, skipping");
+            return Ok(());
+        }
+
+        let (radarr_movie, is_4k) = self.find_radarr_movie(&download_event).await?;
+
+        if !radarr_movie.monitored.unwrap_or(false) {
+            debug!(
+                "Radarr movie {} is not monitored, skipping",
+                download_event.movie.title
+            );
+            return Ok(());
+        }
+
+        let has_file =
+            radarr_movie.has_file.flatten().unwrap_or(false) || radarr_movie.movie_file.is_some();
+        if !has_file {
+            debug!(
+                "Radarr movie {} has no file, skipping",
+                download_event.movie.title
+            );
+            return Ok(());
+        }
+
+        if let Some(requested_movie) = self.get_movie_request(download_event.movie.tmdb_id, is_4k) {
+            self.notify(
+                NotificationData::builder()
+                    .maybe_payload(serde_json::to_value(&download_event).ok())
+                    .r#type(NotificationType::MediaAvailable)
+                    .media_request(requested_movie.clone())
+                    .movie_metadata(MovieMetadata::from_radarr(&radarr_movie))
+                    .build(),
+            )
+            .await;
+            self.remove_request(&requested_movie);
+        }
+
+        Ok(())
+    }
+
+    /// Retrieves the series from the Sonarr instance that sent the event. The instance is taken
+    /// from the webhook path if present, otherwise it is resolved from the event and if that
+    /// fails all the instances are queried
+    async fn find_sonarr_series(
+        &self,
+        instance_path: Option<&str>,
+        event: &sonarr_webhook::DownloadEvent,
+    ) -> Result<(SonarrSeriesResource, bool)> {
+        let mut instances = match instance_path {
+            Some(path) => {
+                let instance = self
+                    .sonarr_apis
+                    .iter()
+                    .find(|instance| {
+                        instance
+                            .name
+                            .as_deref()
+                            .is_some_and(|name| name.trim().eq_ignore_ascii_case(path.trim()))
+                    })
+                    .ok_or(anyhow!("Unknown Sonarr instance '{path}'"))?;
+                vec![instance]
+            }
+            None => self
+                .sonarr_apis
+                .iter()
+                .filter(|instance| {
+                    instance.matches_event(&event.instance_name, &event.application_url)
+                })
+                .collect::<Vec<&SonarrInstance>>(),
+        };
+        if instances.is_empty() {
+            trace!(
+                "Could not resolve Sonarr instance '{}', looking up all instances",
+                event.instance_name
+            );
+            instances = self.sonarr_apis.iter().collect();
+        }
+
+        for instance in instances {
+            if let Ok(res) = instance
+                .api
+                .series_api()
+                .api_v3_series_id_get(
+                    ApiV3SeriesIdGetParams::builder()
+                        .id(event.series.id)
                         .build(),
                 )
-                .await;
-            self.remove_request(&requested_movie);
+                .await
+            {
+                // Series ids are local to each instance, make sure it is the same show
+                if res.tvdb_id == Some(event.series.tvdb_id) {
+                    return Ok((res, instance.is_4k));
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "Could not find Sonarr series with id {}",
+            event.series.id
+        ))
+    }
+
+    /// Retrieves the movie from the Radarr instance that sent the event, if the instance can
+    /// not be resolved from the event all the instances are queried
+    async fn find_radarr_movie(
+        &self,
+        event: &radarr_webhook::DownloadEvent,
+    ) -> Result<(RadarrMovieResource, bool)> {
+        let mut instances = self
+            .radarr_apis
+            .iter()
+            .filter(|instance| instance.matches_event(&event.instance_name, &event.application_url))
+            .collect::<Vec<&RadarrInstance>>();
+        if instances.is_empty() {
+            trace!(
+                "Could not resolve Radarr instance '{}', looking up all instances",
+                event.instance_name
+            );
+            instances = self.radarr_apis.iter().collect();
+        }
+
+        for instance in instances {
+            if let Ok(res) = instance
+                .api
+                .movie_api()
+                .api_v3_movie_id_get(ApiV3MovieIdGetParams::builder().id(event.movie.id).build())
+                .await
+            {
+                // Movie ids are local to each instance, make sure it is the same movie
+                if res.tmdb_id == Some(event.movie.tmdb_id) {
+                    return Ok((res, instance.is_4k));
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "Could not find Radarr movie with id {}",
+            event.movie.id
+        ))
+    }
+
+    async fn process_lidarr(&self, event: LidarrEvent) -> Result<()> {
+        let download_event = match event {
+            LidarrEvent::AlbumDownload(event) => event,
+            _ => return Ok(()),
+        };
+
+        if download_event.is_upgrade {
+            debug!("Is upgrade, skipping");
+            return Ok(());
+        }
+
+        let requesters = self.get_music_requesters(&download_event.artist);
+        if requesters.is_empty() {
+            debug!(
+                "No requesters for artist {}, skipping",
+                download_event.artist.name
+            );
+            return Ok(());
+        }
+
+        let lidarr_album = self.find_lidarr_album(&download_event).await?;
+        if !lidarr_album.monitored.unwrap_or(false) {
+            debug!(
+                "Lidarr album {} is not monitored, skipping",
+                download_event.album.title
+            );
+            return Ok(());
+        }
+
+        let album = &download_event.album;
+        let image_url = find_lidarr_image(album.images.as_deref(), "cover")
+            .or_else(|| {
+                lidarr_album
+                    .images
+                    .clone()
+                    .flatten()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find_map(|image| image.remote_url.flatten())
+            })
+            .or_else(|| find_lidarr_image(download_event.artist.images.as_deref(), "poster"))
+            .and_then(|url| Url::parse(&url).ok());
+
+        let album_metadata = AlbumMetadata {
+            artist: download_event.artist.name.clone(),
+            album_type: album.album_type.clone(),
+            release_date: album
+                .release_date
+                .as_deref()
+                .and_then(|date| OffsetDateTime::parse(date, &Rfc3339).ok())
+                .map(|date| date.date().to_string()),
+            track_count: download_event
+                .tracks
+                .as_ref()
+                .map_or(download_event.track_files.len(), |tracks| tracks.len()),
+        };
+
+        for requester in requesters {
+            let album_request = MediaRequest {
+                request_id: None,
+                r#type: MediaType::ALBUM,
+                media: MediaInfo {
+                    // Music is not tracked by TMDB/TVDB
+                    tmdb_id: 0,
+                    tvdb_id: None,
+                    title: album.title.clone(),
+                    overview: album
+                        .overview
+                        .clone()
+                        .or_else(|| download_event.artist.overview.clone())
+                        .unwrap_or_default(),
+                    translations: BTreeMap::new(),
+                },
+                created_at: OffsetDateTime::now_utc(),
+                requested_by: User {
+                    display_name: requester.display_name,
+                    discord_id: requester.discord_id,
+                    locale: requester.locale,
+                },
+                image_url: image_url.clone(),
+                seasons: None,
+                is_4k: false,
+            };
+
+            info!(
+                "Sending notification for album {} available",
+                album_request.media.title
+            );
+            self.notify(
+                NotificationData::builder()
+                    .maybe_payload(serde_json::to_value(&download_event).ok())
+                    .r#type(NotificationType::AlbumAvailable)
+                    .media_request(album_request)
+                    .album_metadata(album_metadata.clone())
+                    .build(),
+            )
+            .await;
         }
 
         Ok(())
     }
 
-    fn get_tv_request(&self, tmdb_id: i32, tvdb_id: Option<i32>) -> Option<&MediaRequest> {
-        let mut requested_shows = self
-            .requested
+    async fn process_readarr(&self, event: ReadarrEvent) -> Result<()> {
+        let download_event = match event {
+            ReadarrEvent::Download(event) => event,
+            _ => return Ok(()),
+        };
+
+        if download_event.is_upgrade {
+            debug!("Is upgrade, skipping");
+            return Ok(());
+        }
+
+        let user_ids =
+            get_requester_ids_from_tags(download_event.author.tags.as_deref().unwrap_or_default());
+        if user_ids.is_empty() {
+            debug!(
+                "No requester tags for author {}, skipping",
+                download_event.author.name
+            );
+            return Ok(());
+        }
+
+        let book = &download_event.book;
+        let image_url = find_readarr_image(book.images.as_deref(), "cover")
+            .or_else(|| find_readarr_image(download_event.author.images.as_deref(), "poster"))
+            .and_then(|url| Url::parse(&url).ok());
+
+        let format = if download_event
+            .book_files
             .iter()
-            .filter(|request| request.r#type == MediaType::TV);
+            .any(|file| BookFormat::from_file(file) == BookFormat::Audiobook)
+        {
+            BookFormat::Audiobook
+        } else {
+            BookFormat::Ebook
+        };
+        let book_metadata = BookMetadata {
+            author: download_event.author.name.clone(),
+            format,
+            release_date: book
+                .release_date
+                .as_deref()
+                .and_then(|date| OffsetDateTime::parse(date, &Rfc3339).ok())
+                .map(|date| date.date().to_string()),
+        };
 
-        requested_shows.find(|request| {
-            request.media.tmdb_id == tmdb_id
-                && request.media.tvdb_id.is_some_and(|id| Some(id) == tvdb_id)
-        })
+        for user_id in user_ids {
+            let user = match self.get_user(user_id).await {
+                Ok(user) => user,
+                Err(e) => {
+                    warn!("Could not get requester of book {}: {e}", book.title);
+                    continue;
+                }
+            };
+
+            let book_request = MediaRequest {
+                request_id: None,
+                r#type: MediaType::BOOK,
+                media: MediaInfo {
+                    // Books are not tracked by TMDB/TVDB
+                    tmdb_id: 0,
+                    tvdb_id: None,
+                    title: book.title.clone(),
+                    overview: book.overview.clone().unwrap_or_default(),
+                    translations: BTreeMap::new(),
+                },
+                created_at: OffsetDateTime::now_utc(),
+                requested_by: user,
+                image_url: image_url.clone(),
+                seasons: None,
+                is_4k: false,
+            };
+
+            info!(
+                "Sending notification for book {} available",
+                book_request.media.title
+            );
+            self.notify(
+                NotificationData::builder()
+                    .maybe_payload(serde_json::to_value(&download_event).ok())
+                    .r#type(NotificationType::BookAvailable)
+                    .media_request(book_request)
+                    .book_metadata(book_metadata.clone())
+                    .build(),
+            )
+            .await;
+        }
+
+        Ok(())
     }
 
-    fn get_movie_request(&self, tmdb_id: i32) -> Option<&MediaRequest> {
-        let mut requested_movies = self
+    /// Retrieves the users that should be notified about a new album of the artist, either
+    /// because they follow the artist or because the artist has one of their tags
+    fn get_music_requesters(&self, artist: &lidarr_webhook::Artist) -> Vec<MusicRequesterConfig> {
+        let artist_tags = artist.tags.as_deref().unwrap_or_default();
+
+        self.music_requesters
+            .iter()
+            .filter(|requester| {
+                let follows_artist = requester.artists.iter().any(|name| {
+                    name.eq_ignore_ascii_case(&artist.name)
+                        || artist
+                            .mb_id
+                            .as_deref()
+                            .is_some_and(|mb_id| name.eq_ignore_ascii_case(mb_id))
+                });
+                let has_tag = requester
+                    .tags
+                    .iter()
+                    .any(|tag| artist_tags.iter().any(|t| tag.eq_ignore_ascii_case(t)));
+                follows_artist || has_tag
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Retrieves the album from the Lidarr instance that sent the event, if the instance can
+    /// not be resolved from the event all the instances are queried
+    async fn find_lidarr_album(
+        &self,
+        event: &lidarr_webhook::AlbumDownloadEvent,
+    ) -> Result<LidarrAlbumResource> {
+        let mut instances = self
+            .lidarr_apis
+            .iter()
+            .filter(|instance| instance.matches_event(&event.instance_name, &event.application_url))
+            .collect::<Vec<&LidarrInstance>>();
+        if instances.is_empty() {
+            trace!(
+                "Could not resolve Lidarr instance '{}', looking up all instances",
+                event.instance_name
+            );
+            instances = self.lidarr_apis.iter().collect();
+        }
+
+        for instance in instances {
+            if let Ok(res) = instance
+                .api
+                .album_api()
+                .api_v1_album_id_get(ApiV1AlbumIdGetParams::builder().id(event.album.id).build())
+                .await
+            {
+                // Album ids are local to each instance, make sure it is the same album
+                if event.album.mb_id.is_none()
+                    || res.foreign_album_id.clone().flatten() == event.album.mb_id
+                {
+                    return Ok(res);
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "Could not find Lidarr album with id {}",
+            event.album.id
+        ))
+    }
+
+    fn get_tv_request(
+        &self,
+        tmdb_id: i32,
+        tvdb_id: Option<i32>,
+        is_4k: bool,
+    ) -> Option<MediaRequest> {
+        let requested = self.state.requested.read().unwrap();
+        let mut requested_shows = requested
+            .iter()
+            .filter(|request| request.r#type == MediaType::TV && request.is_4k == is_4k);
+
+        requested_shows
+            .find(|request| {
+                request.media.tmdb_id == tmdb_id
+                    && request.media.tvdb_id.is_some_and(|id| Some(id) == tvdb_id)
+            })
+            .cloned()
+    }
+
+    fn get_movie_request(&self, tmdb_id: i32, is_4k: bool) -> Option<MediaRequest> {
+        let requested = self.state.requested.read().unwrap();
+        let mut requested_movies = requested
+            .iter()
+            .filter(|request| request.r#type == MediaType::MOVIE && request.is_4k == is_4k);
+
+        requested_movies
+            .find(|request| request.media.tmdb_id == tmdb_id)
+            .cloned()
+    }
+
+    fn get_request_by_id(&self, request_id: i32) -> Option<MediaRequest> {
+        self.state
             .requested
+            .read()
+            .unwrap()
             .iter()
-            .filter(|request| request.r#type == MediaType::MOVIE);
+            .find(|request| request.request_id == Some(request_id))
+            .cloned()
+    }
+
+    fn add_request(&self, media_request: MediaRequest) {
+        self.change_requests(RequestChange::Add(media_request));
+    }
+
+    fn remove_request(&self, media_request: &MediaRequest) {
+        self.change_requests(RequestChange::Remove(media_request.clone()));
+    }
+
+    fn change_requests(&self, change: RequestChange) {
+        let mut requested = self.state.requested.write().unwrap();
+        change.apply(&mut requested);
+        if let Some(journal) = self.state.sync_journal.lock().unwrap().as_mut() {
+            journal.push(change);
+        }
+    }
+
+    async fn get_movie_by_id(
+        &self,
+        tmdb_id: i32,
+        language: Option<&str>,
+    ) -> Result<SeerrMovieDetails> {
+        let language = language.map(|v| v.to_string());
+        let fetch = self.seerr_api.movies_api().movie_movie_id_get(
+            MovieMovieIdGetParams::builder()
+                .movie_id(tmdb_id as f64)
+                .maybe_language(language.clone())
+                .build(),
+        );
+        self.cache
+            .movies
+            .get_or_fetch((tmdb_id, language), fetch)
+            .await
+            .map_err(|e| anyhow!("Could not get movie with id {tmdb_id}: {e}"))
+    }
+
+    async fn get_show_by_id(&self, tmdb_id: i32, language: Option<&str>) -> Result<SeerrTvDetails> {
+        let language = language.map(|v| v.to_string());
+        let fetch = self.seerr_api.tv_api().tv_tv_id_get(
+            TvTvIdGetParams::builder()
+                .tv_id(tmdb_id as f64)
+                .maybe_language(language.clone())
+                .build(),
+        );
+        self.cache
+            .shows
+            .get_or_fetch((tmdb_id, language), fetch)
+            .await
+            .map_err(|e| anyhow!("Could not get show with id {tmdb_id}: {e}"))
+    }
 
-        requested_movies.find(|request| request.media.tmdb_id == tmdb_id)
+    /// Title and overview of the media in the locales of the destinations, the requester locale
+    /// is left out since the media details are already fetched in it
+    async fn get_media_translations(
+        &self,
+        media_type: &str,
+        tmdb_id: i32,
+        requester_locale: Option<&str>,
+    ) -> BTreeMap<String, MediaText> {
+        let mut translations = BTreeMap::new();
+        for locale in self.notifier.configured_locales() {
+            if Some(locale) == requester_locale {
+                continue;
+            }
+            let text = if media_type == "tv" {
+                self.get_show_by_id(tmdb_id, Some(locale))
+                    .await
+                    .map(|show| MediaText {
+                        title: show.name.or(show.original_name).unwrap_or_default(),
+                        overview: show.overview.unwrap_or_default(),
+                    })
+            } else {
+                self.get_movie_by_id(tmdb_id, Some(locale))
+                    .await
+                    .map(|movie| MediaText {
+                        title: movie.title.or(movie.original_title).unwrap_or_default(),
+                        overview: movie.overview.unwrap_or_default(),
+                    })
+            };
+            match text {
+                Ok(text) => {
+                    translations.insert(locale.to_string(), text);
+                }
+                Err(e) => {
+                    warn!("Could not get the {locale} details of {media_type} {tmdb_id}: {e}")
+                }
+            }
+        }
+        translations
     }
 
-    fn remove_request(&mut self, media_request: &MediaRequest) {
-        if let Some(pos) = self.requested.iter().position(|stored_request| {
-            self.get_media_same(&stored_request.media, &media_request.media)
-        }) {
-            self.requested.remove(pos);
+    /// Retrieves the episode details, the webhook data is preferred as it is already localized
+    /// by Sonarr and the missing details are taken from the Seerr season
+    async fn get_episode_metadata(
+        &self,
+        tmdb_id: i32,
+        webhook_episodes: &[sonarr_webhook::Episode],
+        season_number: i32,
+        episode_number: i32,
+    ) -> EpisodeMetadata {
+        let webhook_episode = webhook_episodes.iter().find(|episode| {
+            episode.season_number == season_number && episode.episode_number == episode_number
+        });
+
+        let seerr_episode = match self
+            .seerr_api
+            .tv_api()
+            .tv_tv_id_season_season_number_get(
+                TvTvIdSeasonSeasonNumberGetParams::builder()
+                    .tv_id(tmdb_id as f64)
+                    .season_number(season_number as f64)
+                    .build(),
+            )
+            .await
+        {
+            Ok(season) => season
+                .episodes
+                .unwrap_or_default()
+                .into_iter()
+                .find(|episode| episode.episode_number == Some(episode_number as f64)),
+            Err(e) => {
+                warn!("Could not get season {season_number} of show with id {tmdb_id}: {e}");
+                None
+            }
+        };
+
+        let non_empty = |value: &String| !value.trim().is_empty();
+
+        EpisodeMetadata {
+            title: webhook_episode
+                .map(|episode| episode.title.clone())
+                .filter(non_empty)
+                .or_else(|| {
+                    seerr_episode
+                        .as_ref()
+                        .and_then(|episode| episode.name.clone())
+                })
+                .filter(non_empty),
+            overview: webhook_episode
+                .and_then(|episode| episode.overview.clone())
+                .filter(non_empty)
+                .or_else(|| {
+                    seerr_episode
+                        .as_ref()
+                        .and_then(|episode| episode.overview.clone())
+                })
+                .filter(non_empty),
+            air_date: webhook_episode
+                .and_then(|episode| episode.air_date.clone())
+                .or_else(|| {
+                    seerr_episode
+                        .as_ref()
+                        .and_then(|episode| episode.air_date.clone().flatten())
+                })
+                .filter(non_empty),
+            still_url: seerr_episode
+                .and_then(|episode| episode.still_path.flatten())
+                .and_then(|path| {
+                    Url::parse(&format!("https://image.tmdb.org/t/p/w780{}", path)).ok()
+                }),
         }
     }
 
-    fn get_media_same(&self, media_1: &MediaInfo, media_2: &MediaInfo) -> bool {
-        media_1.tmdb_id == media_2.tmdb_id
-            || media_1.tvdb_id.is_some_and(|v| Some(v) == media_2.tvdb_id)
+    async fn get_user(&self, user_id: i32) -> Result<User> {
+        let fetch = self
+            .seerr_api
+            .users_api()
+            .user_user_id_get(UserUserIdGetParams::builder().user_id(user_id).build());
+        let user = self
+            .cache
+            .users
+            .get_or_fetch(user_id, fetch)
+            .await
+            .map_err(|e| anyhow!("Could not get user with id {user_id}: {e}"))?;
+
+        let display_name = user
+            .display_name
+            .or_else(|| user.username.flatten())
+            .or_else(|| user.jellyfin_username.flatten())
+            .or_else(|| user.plex_username.flatten())
+            .clone()
+            .context("Display name not set")?;
+        let locale = user
+            .settings
+            .as_ref()
+            .and_then(|settings| settings.locale.clone().flatten());
+        let discord_id = user.settings.and_then(|settings| {
+            settings.notification_types.clone().and_then(|types| {
+                if types.discord.is_some_and(|v| v == 8.0) {
+                    settings.discord_id.unwrap_or_default()
+                } else {
+                    None
+                }
+            })
+        });
+
+        Ok(User {
+            display_name,
+            discord_id,
+            locale,
+        })
     }
 
-    async fn get_movie_by_id(&self, tmdb_id: i32) -> Result<SeerrMovieDetails> {
-        self.seerr_api
-            .movies_api()
-            .movie_movie_id_get(
-                MovieMovieIdGetParams::builder()
-                    .movie_id(tmdb_id as f64)
-                    .build(),
-            )
-            .await
-            .map_err(|e| anyhow!("Could not get movie with id {tmdb_id}: {e}"))
-    }
+    /// Syncs the requests with Seerr, only the ones modified since the previous sync are
+    /// fetched except once a day, when the stored requests are rebuilt
+    async fn fetch_requests(&self) -> Result<()> {
+        let updated_since = {
+            let sync = self.state.sync.lock().unwrap();
+            sync.updated_at.filter(|_| {
+                sync.full_sync_at
+                    .is_some_and(|full_sync_at| full_sync_at.elapsed() < FULL_SYNC_INTERVAL)
+            })
+        };
+
+        let started_at = Instant::now();
+        let latest_update = match updated_since {
+            Some(updated_since) => self.sync_modified_requests(updated_since).await?,
+            None => self.sync_all_requests().await?,
+        };
+
+        let mut sync = self.state.sync.lock().unwrap();
+        if updated_since.is_none() {
+            sync.full_sync_at = Some(started_at);
+        }
+        sync.updated_at = sync.updated_at.max(latest_update);
+        Ok(())
+    }
+
+    /// Replaces the stored requests with the approved ones, returns their latest modification
+    ///
+    /// The changes made by the events meanwhile are applied again on the fetched requests.
+    async fn sync_all_requests(&self) -> Result<Option<OffsetDateTime>> {
+        *self.state.sync_journal.lock().unwrap() = Some(Vec::new());
+        let result = self.fetch_pending_requests().await;
+
+        let mut requested = self.state.requested.write().unwrap();
+        let changes = self
+            .state
+            .sync_journal
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap_or_default();
+        let (mut fetched, latest_update) = result?;
+        for change in &changes {
+            change.apply(&mut fetched);
+        }
+
+        info!("Synced {} pending requests from Seerr", fetched.len());
+        *requested = fetched;
+        Ok(latest_update)
+    }
+
+    /// Approved requests of Seerr whose media is not available yet, with their latest
+    /// modification
+    async fn fetch_pending_requests(&self) -> Result<(Vec<MediaRequest>, Option<OffsetDateTime>)> {
+        let mut fetched = Vec::new();
+        let mut latest_update = None;
+
+        let num_requests = self
+            .seerr_api
+            .request_api()
+            .request_count_get()
+            .await?
+            .approved
+            .unwrap_or(0.0) as usize;
+
+        let batch_size = 100;
+        let mut offset = 0;
+
+        while offset < num_requests {
+            let requests = self
+                .seerr_api
+                .request_api()
+                .request_get(
+                    seerr::apis::request_api::RequestGetParams::builder()
+                        .take(batch_size as f64)
+                        .skip(offset as f64)
+                        .filter("approved".into())
+                        .sort("modified".into())
+                        .build(),
+                )
+                .await?
+                .results
+                .unwrap_or_default();
+
+            for media_request in requests {
+                latest_update = latest_update.max(request_updated_at(&media_request));
+                fetched.extend(self.pending_media_request(&media_request).await?);
+            }
+
+            offset += batch_size;
+        }
 
-    async fn get_show_by_id(&self, tmdb_id: i32) -> Result<SeerrTvDetails> {
-        self.seerr_api
-            .tv_api()
-            .tv_tv_id_get(TvTvIdGetParams::builder().tv_id(tmdb_id as f64).build())
-            .await
-            .map_err(|e| anyhow!("Could not get show with id {tmdb_id}: {e}"))
+        Ok((fetched, latest_update))
     }
 
-    async fn fetch_requests(&mut self) -> Result<()> {
-        self.requested.clear();
-
-        let num_requests = self
-            .seerr_api
-            .request_api()
-            .request_count_get()
-            .await?
-            .approved
-            .unwrap_or(0.0) as usize;
+    /// Applies the requests modified since `updated_since` to the stored ones, returns their
+    /// latest modification
+    async fn sync_modified_requests(
+        &self,
+        updated_since: OffsetDateTime,
+    ) -> Result<Option<OffsetDateTime>> {
+        let mut latest_update = None;
+        let mut num_synced = 0;
+        let mut synced_ids = HashSet::new();
 
         let batch_size = 100;
+        // The requests modified or deleted while paging shift the next pages, so each page
+        // also rescans the end of the previous one
+        let overlap = 10;
         let mut offset = 0;
 
-        while offset < num_requests {
+        // The requests come most recently modified first, so the sync stops at the first one
+        // older than the previous sync. The ones modified at the same time are synced again
+        // since they may have been missed.
+        'pages: loop {
             let requests = self
                 .seerr_api
                 .request_api()
@@ -896,41 +3026,138 @@ impl RequestHandler {
                     seerr::apis::request_api::RequestGetParams::builder()
                         .take(batch_size as f64)
                         .skip(offset as f64)
-                        .filter("approved".into())
+                        .filter("all".into())
+                        .sort("modified".into())
                         .build(),
                 )
                 .await?
                 .results
                 .unwrap_or_default();
+            let is_last_page = requests.len() < batch_size;
 
             for media_request in requests {
-                self.process_request(media_request).await?;
+                let Some(updated_at) =
+                    request_updated_at(&media_request).filter(|date| *date >= updated_since)
+                else {
+                    break 'pages;
+                };
+                if let Some(id) = media_request.id
+                    && !synced_ids.insert(id as i32)
+                {
+                    continue;
+                }
+                latest_update = latest_update.max(Some(updated_at));
+                self.sync_request(&media_request).await?;
+                num_synced += 1;
             }
 
-            offset += batch_size;
+            if is_last_page {
+                break;
+            }
+            offset += batch_size - overlap;
+        }
+
+        debug!("Synced {num_synced} modified requests from Seerr");
+        Ok(latest_update)
+    }
+
+    /// Stores the request if it is approved and its media is not available yet, otherwise
+    /// removes it
+    async fn sync_request(&self, media_request: &SeerrMediaRequest) -> Result<()> {
+        // if APPROVED
+        let pending_request = if media_request.status == Some(2.0) {
+            self.pending_media_request(media_request).await?
+        } else {
+            None
+        };
+
+        match pending_request {
+            Some(pending_request) => self.add_request(pending_request),
+            None => {
+                let stored_request = media_request
+                    .id
+                    .and_then(|id| self.get_request_by_id(id as i32));
+                if let Some(stored_request) = stored_request {
+                    info!("Request Removed: {}", stored_request.media.title);
+                    self.remove_request(&stored_request);
+                }
+            }
         }
+        Ok(())
+    }
 
+    async fn process_request(&self, media_request: SeerrMediaRequest) -> Result<()> {
+        if let Some(processed_request) = self.pending_media_request(&media_request).await? {
+            self.add_request(processed_request);
+        }
         Ok(())
     }
 
-    async fn process_request(&mut self, media_request: SeerrMediaRequest) -> Result<()> {
+    /// Converts the Seerr request if its media is a movie or show that is not available yet
+    async fn pending_media_request(
+        &self,
+        media_request: &SeerrMediaRequest,
+    ) -> Result<Option<MediaRequest>> {
         trace!("Processing request: {:?}", media_request);
         let media_type = media_request
             .r#type
-            .as_ref()
+            .as_deref()
             .context("Could not get media type")?;
+        if media_type != "tv" && media_type != "movie" {
+            return Ok(None);
+        }
+
         let media = media_request
             .media
             .as_ref()
             .context("Could not get media")?;
 
+        let is_4k = media_request.is4k.unwrap_or(false);
+
         // if AVAILABLE
-        if media.status.unwrap_or(0) == 5 {
-            return Ok(());
+        let status = if is_4k { media.status4k } else { media.status };
+        if status.unwrap_or(0) == 5 {
+            return Ok(None);
+        }
+
+        let (processed_request, available_seasons) =
+            self.build_media_request(media_request).await?;
+
+        if let Some(seasons) = &processed_request.seasons {
+            let seasons_missing = seasons
+                .iter()
+                .filter(|s| !available_seasons.contains(&s.season_number))
+                .count();
+            if seasons_missing == 0 {
+                return Ok(None);
+            }
         }
 
+        info!("Request Added: {}", processed_request.media.title);
+        debug!("{:?}", processed_request);
+        Ok(Some(processed_request))
+    }
+
+    /// Converts the Seerr request fetching its requester and media details, the seasons of the
+    /// show already available are returned along with it
+    async fn build_media_request(
+        &self,
+        media_request: &SeerrMediaRequest,
+    ) -> Result<(MediaRequest, Vec<i32>)> {
+        let media_type = media_request
+            .r#type
+            .as_ref()
+            .context("Could not get media type")?;
+        let media = media_request
+            .media
+            .as_ref()
+            .context("Could not get media")?;
+
+        let is_4k = media_request.is4k.unwrap_or(false);
+
         let tmdb_id = media.tmdb_id.context("Could not get media tmdb_id")?;
         let tvdb_id = media.tvdb_id.unwrap_or_default();
+        let request_id = media_request.id.map(|id| id as i32);
 
         let created_at = media_request
             .created_at
@@ -944,35 +3171,16 @@ impl RequestHandler {
             .map(|user| user.id)
             .context("Could not get user id")?;
 
-        let user = self
-            .seerr_api
-            .users_api()
-            .user_user_id_get(UserUserIdGetParams::builder().user_id(user_id).build())
-            .await
-            .map_err(|e| anyhow!("Could not get user with id {user_id}: {e}"))?;
-
-        let display_name = user
-            .display_name
-            .or_else(|| user.username.flatten())
-            .or_else(|| user.jellyfin_username.flatten())
-            .or_else(|| user.plex_username.flatten())
-            .clone()
-            .context("Display name not set")?;
-        let discord_id = user.settings.and_then(|settings| {
-            settings.notification_types.clone().and_then(|types| {
-                if types.discord.is_some_and(|v| v == 8.0) {
-                    settings.discord_id.unwrap_or_default()
-                } else {
-                    None
-                }
-            })
-        });
+        let user = self.get_user(user_id).await?;
 
         if media_type == "tv" {
             let show = self
-                .get_show_by_id(tmdb_id)
+                .get_show_by_id(tmdb_id, user.locale.as_deref())
                 .await
                 .map_err(|e| anyhow!("Could not get show from Seerr: {e}"))?;
+            let translations = self
+                .get_media_translations(media_type, tmdb_id, user.locale.as_deref())
+                .await;
 
             let requested_seasons = media_request
                 .seasons
@@ -993,7 +3201,8 @@ impl RequestHandler {
                 .unwrap_or_default()
                 .iter()
                 .filter_map(|s| {
-                    if s.status == Some(5) {
+                    let status = if is_4k { s.status4k } else { s.status };
+                    if status == Some(5) {
                         s.season_number.map(|n| n as i32)
                     } else {
                         None
@@ -1001,46 +3210,41 @@ impl RequestHandler {
                 })
                 .collect::<Vec<i32>>();
 
-            let seasons_missing = requested_seasons
-                .iter()
-                .filter(|s| !available_seasons.contains(&s.season_number))
-                .collect::<Vec<&SeasonInfo>>();
-
-            if !seasons_missing.is_empty() {
-                let processed_request = MediaRequest {
-                    r#type: MediaType::TV,
-                    media: MediaInfo {
-                        tmdb_id: tmdb_id,
-                        tvdb_id: tvdb_id,
-                        title: show.name.or_else(|| show.original_name).unwrap_or_default(),
-                        overview: show.overview.unwrap_or_default(),
-                    },
-                    created_at: created_at,
-                    requested_by: User {
-                        display_name: display_name,
-                        discord_id: discord_id,
-                    },
-                    image_url: show.poster_path.and_then(|path| {
-                        Url::parse(&format!(
-                            "https://image.tmdb.org/t/p/w600_and_h900_bestv2{}",
-                            path
-                        ))
-                        .ok()
-                    }),
-                    seasons: Some(requested_seasons),
-                };
+            let processed_request = MediaRequest {
+                request_id,
+                r#type: MediaType::TV,
+                media: MediaInfo {
+                    tmdb_id: tmdb_id,
+                    tvdb_id: tvdb_id,
+                    title: show.name.or_else(|| show.original_name).unwrap_or_default(),
+                    overview: show.overview.unwrap_or_default(),
+                    translations,
+                },
+                created_at: created_at,
+                requested_by: user,
+                image_url: show.poster_path.and_then(|path| {
+                    Url::parse(&format!(
+                        "https://image.tmdb.org/t/p/w600_and_h900_bestv2{}",
+                        path
+                    ))
+                    .ok()
+                }),
+                seasons: Some(requested_seasons),
+                is_4k,
+            };
 
-                info!("Request Added: {}", processed_request.media.title);
-                debug!("{:?}", processed_request);
-                self.requested.push(processed_request);
-            }
+            Ok((processed_request, available_seasons))
         } else if media_type == "movie" {
             let movie = self
-                .get_movie_by_id(tmdb_id)
+                .get_movie_by_id(tmdb_id, user.locale.as_deref())
                 .await
                 .map_err(|e| anyhow!("Could not get show from Seerr: {e}"))?;
+            let translations = self
+                .get_media_translations(media_type, tmdb_id, user.locale.as_deref())
+                .await;
 
             let processed_request = MediaRequest {
+                request_id,
                 r#type: MediaType::MOVIE,
                 media: MediaInfo {
                     tmdb_id: tmdb_id,
@@ -1050,12 +3254,10 @@ impl RequestHandler {
                         .or_else(|| movie.original_title)
                         .unwrap_or_default(),
                     overview: movie.overview.unwrap_or_default(),
+                    translations,
                 },
                 created_at: created_at,
-                requested_by: User {
-                    display_name: display_name,
-                    discord_id: discord_id,
-                },
+                requested_by: user,
                 image_url: movie.poster_path.and_then(|path| {
                     Url::parse(&format!(
                         "https://image.tmdb.org/t/p/w600_and_h900_bestv2{}",
@@ -1064,14 +3266,13 @@ impl RequestHandler {
                     .ok()
                 }),
                 seasons: None,
+                is_4k,
             };
 
-            info!("Request Added: {}", processed_request.media.title);
-            debug!("{:?}", processed_request);
-            self.requested.push(processed_request);
+            Ok((processed_request, Vec::new()))
+        } else {
+            Err(anyhow!("Unsupported media type {media_type}"))
         }
-
-        Ok(())
     }
 }
 
@@ -1079,6 +3280,45 @@ impl RequestHandler {
 enum MediaType {
     MOVIE,
     TV,
+    ALBUM,
+    BOOK,
+}
+
+/// Retrieves the Seerr user ids from tags using the Seerr tag format `<user id> - <username>`
+fn get_requester_ids_from_tags(tags: &[String]) -> Vec<i32> {
+    tags.iter()
+        .filter_map(|tag| {
+            tag.split_once('-')
+                .and_then(|(id, _)| id.trim().parse::<i32>().ok())
+        })
+        .collect()
+}
+
+fn find_readarr_image(
+    images: Option<&[readarr_webhook::Image]>,
+    cover_type: &str,
+) -> Option<String> {
+    images
+        .unwrap_or_default()
+        .iter()
+        .find(|image| image.cover_type.eq_ignore_ascii_case(cover_type))
+        .and_then(|image| image.remote_url.clone().or_else(|| image.url.clone()))
+}
+
+fn find_lidarr_image(images: Option<&[lidarr_webhook::Image]>, cover_type: &str) -> Option<String> {
+    images
+        .unwrap_or_default()
+        .iter()
+        .find(|image| image.cover_type.eq_ignore_ascii_case(cover_type))
+        .and_then(|image| image.remote_url.clone().or_else(|| image.url.clone()))
+}
+
+/// Last modification of the Seerr request
+fn request_updated_at(media_request: &SeerrMediaRequest) -> Option<OffsetDateTime> {
+    media_request
+        .updated_at
+        .as_deref()
+        .and_then(|date| OffsetDateTime::parse(date, &Rfc3339).ok())
 }
 
 #[derive(Debug, Clone)]
@@ -1092,78 +3332,543 @@ struct MediaInfo {
     tvdb_id: Option<i32>,
     title: String,
     overview: String,
+    /// Title and overview in the locales configured on the destinations, by locale
+    translations: BTreeMap<String, MediaText>,
+}
+
+#[derive(Debug, Clone)]
+struct MediaText {
+    title: String,
+    overview: String,
 }
 
 #[derive(Debug, Clone)]
 struct User {
     display_name: String,
     discord_id: Option<String>,
+    locale: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 struct MediaRequest {
+    /// Id of the Seerr request, none for the music and books notified from the *arr events
+    request_id: Option<i32>,
     r#type: MediaType,
     media: MediaInfo,
     created_at: OffsetDateTime,
     requested_by: User,
     image_url: Option<Url>,
     seasons: Option<Vec<SeasonInfo>>,
+    is_4k: bool,
 }
 
-pub async fn run(
+impl MediaRequest {
+    /// Whether both are the same Seerr request, the ones without id are compared by media
+    fn is_same_request(&self, other: &MediaRequest) -> bool {
+        match (self.request_id, other.request_id) {
+            (Some(id), Some(other_id)) => id == other_id,
+            _ => {
+                self.is_4k == other.is_4k
+                    && (self.media.tmdb_id == other.media.tmdb_id
+                        || self
+                            .media
+                            .tvdb_id
+                            .is_some_and(|v| Some(v) == other.media.tvdb_id))
+            }
+        }
+    }
+}
+
+/// Change of the stored requests made by an event
+enum RequestChange {
+    /// Stores the request, replacing the previous version of the same Seerr request
+    Add(MediaRequest),
+    Remove(MediaRequest),
+}
+
+impl RequestChange {
+    fn apply(&self, requested: &mut Vec<MediaRequest>) {
+        match self {
+            Self::Add(media_request) => {
+                requested.retain(|stored_request| !stored_request.is_same_request(media_request));
+                requested.push(media_request.clone());
+            }
+            Self::Remove(media_request) => {
+                if let Some(pos) = requested
+                    .iter()
+                    .position(|stored_request| stored_request.is_same_request(media_request))
+                {
+                    requested.remove(pos);
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for MediaRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?}\t{}\t{}{}",
+            self.r#type,
+            self.media.tmdb_id,
+            self.media.title,
+            if self.is_4k { " (4K)" } else { "" }
+        )?;
+        if let Some(seasons) = &self.seasons {
+            let seasons_joined = seasons
+                .iter()
+                .map(|s| s.season_number.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            write!(f, " [seasons {seasons_joined}]")?;
+        }
+        write!(
+            f,
+            "\trequested by {} on {}",
+            self.requested_by.display_name,
+            self.created_at.date()
+        )
+    }
+}
+
+/// Webhook payload processed by the workers or by the `replay` command
+pub enum WebhookEvent {
+    Sonarr(InstanceEvent<SonarrEvent>),
+    Radarr(RadarrEvent),
+    Lidarr(LidarrEvent),
+    Readarr(ReadarrEvent),
+    Seerr(SeerrEvent),
+    MediaServer(MediaServerEvent),
+}
+
+impl WebhookEvent {
+    /// Events of the same media item are processed in order by the same worker, the ones
+    /// without media item are ordered by source
+    fn ordering_key(&self) -> u64 {
+        let media_id = match self {
+            Self::Sonarr(event) => match &event.event {
+                SonarrEvent::Download(download) => Some(download.series.tmdb_id),
+                _ => None,
+            },
+            Self::Radarr(RadarrEvent::Download(download)) => Some(download.movie.tmdb_id),
+            Self::Lidarr(LidarrEvent::AlbumDownload(download)) => Some(download.album.id),
+            Self::Readarr(ReadarrEvent::Download(download)) => Some(download.book.id),
+            Self::Seerr(event) => event.media.as_ref().and_then(|media| media.tmdb_id),
+            Self::MediaServer(event) => event.tmdb_id.or(event.tvdb_id),
+            _ => None,
+        };
+
+        let mut hasher = DefaultHasher::new();
+        match media_id {
+            Some(media_id) => media_id.hash(&mut hasher),
+            None => self.source().hash(&mut hasher),
+        }
+        hasher.finish()
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            Self::Sonarr(_) => "Sonarr",
+            Self::Radarr(_) => "Radarr",
+            Self::Lidarr(_) => "Lidarr",
+            Self::Readarr(_) => "Readarr",
+            Self::Seerr(_) => "Seerr",
+            Self::MediaServer(_) => "media server",
+        }
+    }
+}
+
+/// Processes a single webhook event as if it was received by the server, the notifications
+/// are queued without waiting for the media server or the digest windows
+pub async fn replay(
+    app_config: AppConfig,
+    templates: MessageTemplates,
+    queue: Arc<NotificationQueue>,
+    event: WebhookEvent,
+) -> Result<()> {
+    let cache = Arc::new(MetadataCache::new(&app_config.seerr));
+    let request_handler =
+        RequestHandler::new(app_config, templates, queue, Arc::default(), cache).await?;
+    request_handler.process(event).await?;
+    request_handler.flush().await;
+    Ok(())
+}
+
+/// Requests fetched from Seerr that are waiting to be available, one line per request
+pub async fn pending_requests(
     app_config: AppConfig,
-    mut sonarr_rx: mpsc::UnboundedReceiver<SonarrEvent>,
-    mut radarr_rx: mpsc::UnboundedReceiver<RadarrEvent>,
-    mut seerr_rx: mpsc::UnboundedReceiver<SeerrEvent>,
-    close_tx: watch::Sender<bool>,
+    templates: MessageTemplates,
+    queue: Arc<NotificationQueue>,
+) -> Result<Vec<String>> {
+    let cache = Arc::new(MetadataCache::new(&app_config.seerr));
+    let request_handler =
+        RequestHandler::new(app_config, templates, queue, Arc::default(), cache).await?;
+    let requested = request_handler.state.requested.read().unwrap();
+    Ok(requested
+        .iter()
+        .map(|request| request.to_string())
+        .collect())
+}
+
+/// Queues a sample notification for every configured destination
+pub async fn send_test(
+    app_config: AppConfig,
+    templates: MessageTemplates,
+    queue: Arc<NotificationQueue>,
+) -> Result<()> {
+    let mut destinations = Vec::new();
+    if app_config.discord.is_some() {
+        destinations.push(Destination::Discord);
+    }
+    if app_config.telegram.is_some() {
+        destinations.push(Destination::Telegram);
+    }
+    if destinations.is_empty() {
+        return Err(anyhow!("Neither Discord nor Telegram are configured"));
+    }
+
+    let cache = Arc::new(MetadataCache::new(&app_config.seerr));
+    let notifier = NotificationController::new(
+        app_config.discord,
+        app_config.telegram,
+        templates,
+        queue,
+        cache,
+    );
+    let data = NotificationData::builder()
+        .r#type(NotificationType::MediaAvailable)
+        .media_request(MediaRequest {
+            request_id: None,
+            r#type: MediaType::MOVIE,
+            media: MediaInfo {
+                tmdb_id: 0,
+                tvdb_id: None,
+                title: "Informarr".to_string(),
+                overview: "Test notification sent by informarr".to_string(),
+                translations: BTreeMap::new(),
+            },
+            created_at: OffsetDateTime::now_utc(),
+            requested_by: User {
+                display_name: "informarr".to_string(),
+                discord_id: None,
+                locale: None,
+            },
+            image_url: None,
+            seasons: None,
+            is_4k: false,
+        })
+        .build();
+
+    for destination in destinations {
+        notifier.send_to(destination, &data).await;
+    }
+    Ok(())
+}
+
+/// Delay before retrying to connect to the upstreams, doubled on every failure
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Time between two checks of the upstreams while they are healthy
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Longest time to connect to the upstreams when the request handler is created, so a hanging
+/// upstream cannot block the controller
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Events waiting for each worker, the controller stops receiving webhook events while the
+/// queue of the worker of the next event is full
+const WORKER_QUEUE_CAPACITY: usize = 32;
+
+type WorkerJob = (Arc<RequestHandler>, WebhookEvent);
+
+/// Processes the events of its queue one at a time with the handler current when they were
+/// received
+async fn run_worker(mut jobs_rx: mpsc::Receiver<WorkerJob>) {
+    while let Some((request_handler, event)) = jobs_rx.recv().await {
+        if let Err(e) = request_handler.process(event).await {
+            error!("{e}");
+        }
+    }
+}
+
+/// Queues the event for the worker of its media item
+async fn dispatch(
+    workers: &[mpsc::Sender<WorkerJob>],
+    request_handler: &Arc<RequestHandler>,
+    event: WebhookEvent,
+) {
+    let worker = &workers[(event.ordering_key() % workers.len() as u64) as usize];
+    if worker.send((request_handler.clone(), event)).await.is_err() {
+        error!("Worker stopped, dropping event");
+    }
+}
+
+/// Why the request handler is created again in the background
+enum Reload {
+    Config,
+    MissingInstances,
+}
+
+async fn with_connect_timeout<T>(connect: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::time::timeout(CONNECT_TIMEOUT, connect)
+        .await
+        .map_err(|_| anyhow!("Timed out connecting to the upstreams"))?
+}
+
+/// Creates the handler again with the configuration, off the event loop
+fn spawn_reload(
+    request_handler: &Arc<RequestHandler>,
+    config: &LoadedConfig,
+) -> JoinHandle<Result<RequestHandler>> {
+    let handler = request_handler.clone();
+    let (app_config, templates) = (config.app_config.clone(), config.templates.clone());
+    tokio::spawn(async move { with_connect_timeout(handler.reload(app_config, templates)).await })
+}
+
+/// Runs the periodic job in the background unless its previous run is still going
+fn spawn_job(task: &mut Option<JoinHandle<()>>, job: impl Future<Output = ()> + Send + 'static) {
+    if task.as_ref().is_none_or(|task| task.is_finished()) {
+        *task = Some(tokio::spawn(job));
+    }
+}
+
+/// Processes the webhook events, if Seerr or the media server cannot be reached on startup the
+/// connection is retried with backoff while the events are buffered
+///
+/// The events are processed by `workers` tasks, the ones of the same media item always by the
+/// same worker so they are handled in the order they were received.
+pub async fn run(
+    mut config_rx: watch::Receiver<Arc<LoadedConfig>>,
+    mut sonarr_rx: mpsc::Receiver<InstanceEvent<SonarrEvent>>,
+    mut radarr_rx: mpsc::Receiver<RadarrEvent>,
+    mut lidarr_rx: mpsc::Receiver<LidarrEvent>,
+    mut readarr_rx: mpsc::Receiver<ReadarrEvent>,
+    mut seerr_rx: mpsc::Receiver<SeerrEvent>,
+    mut media_server_rx: mpsc::Receiver<MediaServerEvent>,
+    queue: Arc<NotificationQueue>,
+    health: Arc<UpstreamHealth>,
+    cache: Arc<MetadataCache>,
     mut close_rx: watch::Receiver<bool>,
 ) -> Result<()> {
-    let mut request_handler = match RequestHandler::new(app_config).await {
-        Ok(handler) => handler,
-        Err(e) => {
-            let _ = close_tx.send(true);
-            return Err(anyhow!("Failed to initialize request handler: {e}"));
+    let mut is_config_open = true;
+    let mut retry_delay = INITIAL_RETRY_DELAY;
+
+    // The events received meanwhile wait in the channels until the upstreams are reachable
+    let request_handler = loop {
+        let config = config_rx.borrow_and_update().clone();
+        let initial_handler = tokio::select! {
+            result = with_connect_timeout(RequestHandler::new(
+                config.app_config.clone(),
+                config.templates.clone(),
+                queue.clone(),
+                health.clone(),
+                cache.clone(),
+            )) => result,
+            result = close_rx.changed() => {
+                debug!("Closing controller");
+                if result.is_ok() && *close_rx.borrow_and_update() {
+                    return Ok(());
+                }
+                continue;
+            }
+        };
+        match initial_handler {
+            Ok(handler) => break handler,
+            Err(e) => warn!(
+                "Failed to initialize request handler, retrying in {}s: {e}",
+                retry_delay.as_secs()
+            ),
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(retry_delay) => {
+                retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+            }
+            result = config_rx.changed(), if is_config_open => {
+                is_config_open = result.is_ok();
+                retry_delay = INITIAL_RETRY_DELAY;
+            }
+            result = close_rx.changed() => {
+                debug!("Closing controller");
+                if result.is_ok() && *close_rx.borrow_and_update() {
+                    return Ok(());
+                }
+            }
         }
     };
+    let mut request_handler = Arc::new(request_handler);
+    health.set_ready(true);
+    info!("Request handler initialized");
+
+    let worker_count = config_rx.borrow().app_config.webhooks.workers;
+    let (workers, worker_tasks): (Vec<_>, Vec<_>) = (0..worker_count)
+        .map(|_| {
+            let (jobs_tx, jobs_rx) = mpsc::channel(WORKER_QUEUE_CAPACITY);
+            (jobs_tx, tokio::spawn(run_worker(jobs_rx)))
+        })
+        .unzip();
 
-    let scan_interval = Duration::from_secs(60 * 30);
+    let mut scan_interval = request_handler.sync_interval;
     let mut next_scan_requests = Instant::now() + scan_interval;
+    let mut scan_task = None;
+
+    retry_delay = INITIAL_RETRY_DELAY;
+    let mut next_health_check = Instant::now() + HEALTH_CHECK_INTERVAL;
+    let mut health_task = None;
+
+    let mut playback_interval = request_handler
+        .media_server
+        .as_ref()
+        .map(|media_server| media_server.poll_interval());
+    let mut next_playback_check = Instant::now() + playback_interval.unwrap_or(scan_interval);
+    let mut playback_task = None;
+    let mut digest_tasks = JoinSet::new();
+    let mut reload_task: Option<(Reload, JoinHandle<Result<RequestHandler>>)> = None;
 
     loop {
+        let next_digest = request_handler.notifier.next_digest_deadline();
         tokio::select! {
             Some(event) = sonarr_rx.recv() => {
-                info!("Processing Sonarr event: {}", event);
-                debug!("Received from Sonarr: {}", serde_json::to_string(&event).unwrap_or_default());
-                if let Err(e) = request_handler.process_sonarr(event).await {
-                    error!("Failed processing Sonarr event: {e}");
-                }
+                dispatch(&workers, &request_handler, WebhookEvent::Sonarr(event)).await;
             },
             Some(event) = radarr_rx.recv() => {
-                info!("Processing Radarr event: {}", event);
-                debug!("Received from Radarr: {}", serde_json::to_string(&event).unwrap_or_default());
-                if let Err(e) = request_handler.process_radarr(event).await {
-                    error!("Failed processing Radarr event: {e}");
-                }
+                dispatch(&workers, &request_handler, WebhookEvent::Radarr(event)).await;
+            },
+            Some(event) = lidarr_rx.recv() => {
+                dispatch(&workers, &request_handler, WebhookEvent::Lidarr(event)).await;
+            },
+            Some(event) = readarr_rx.recv() => {
+                dispatch(&workers, &request_handler, WebhookEvent::Readarr(event)).await;
             },
             Some(event) = seerr_rx.recv() => {
-                info!("Processing Seerr event: {}", event.notification_type);
-                debug!("Received from Seerr: {}", serde_json::to_string(&event).unwrap_or_default());
-                if let Err(e) = request_handler.process_seerr(event).await {
-                    error!("Failed processing Seerr event: {e}");
-                }
+                dispatch(&workers, &request_handler, WebhookEvent::Seerr(event)).await;
+            }
+            Some(event) = media_server_rx.recv() => {
+                dispatch(&workers, &request_handler, WebhookEvent::MediaServer(event)).await;
+            },
+            _ = tokio::time::sleep_until(next_playback_check.into()), if playback_interval.is_some() => {
+                let handler = request_handler.clone();
+                spawn_job(&mut playback_task, async move {
+                    handler.check_pending_playback(|_| true).await;
+                });
+                next_playback_check = Instant::now() + playback_interval.unwrap_or(scan_interval);
+            }
+            // A digest created by a worker wakes the loop to schedule its window
+            _ = request_handler.notifier.digests.added.notified() => {}
+            _ = tokio::time::sleep_until(next_digest.unwrap_or(next_scan_requests).into()), if next_digest.is_some() => {
+                // The closed digests are removed right away so the deadline moves on while
+                // they are sent
+                let closed = request_handler.notifier.take_closed_digests();
+                let handler = request_handler.clone();
+                while digest_tasks.try_join_next().is_some() {}
+                digest_tasks.spawn(async move {
+                    handler.notifier.send_digests(closed).await;
+                });
             }
             _ = tokio::time::sleep_until(next_scan_requests.into()) => {
-                if let Err(e) = request_handler.fetch_requests().await {
-                    error!("Failed to update requests: {e}");
-                }
+                let handler = request_handler.clone();
+                let health = health.clone();
+                spawn_job(&mut scan_task, async move {
+                    let result = handler.fetch_requests().await;
+                    health.record(health::SEERR, &result);
+                    if let Err(e) = result {
+                        error!("Failed to update requests: {e}");
+                    }
+                });
                 next_scan_requests = Instant::now() + scan_interval;
             }
+            _ = tokio::time::sleep_until(next_health_check.into()) => {
+                // The failing upstreams are checked more often until they are back
+                if health.is_healthy() {
+                    retry_delay = INITIAL_RETRY_DELAY;
+                    next_health_check = Instant::now() + HEALTH_CHECK_INTERVAL;
+                } else {
+                    next_health_check = Instant::now() + retry_delay;
+                    retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                }
+                if request_handler.is_missing_instances && reload_task.is_none() {
+                    let task = spawn_reload(&request_handler, &config_rx.borrow());
+                    reload_task = Some((Reload::MissingInstances, task));
+                }
+                let handler = request_handler.clone();
+                spawn_job(&mut health_task, async move {
+                    handler.check_upstreams().await;
+                });
+            }
+            result = config_rx.changed(), if is_config_open => {
+                if result.is_err() {
+                    is_config_open = false;
+                    continue;
+                }
+                // The new configuration replaces any reload still connecting
+                if let Some((_, task)) = reload_task.take() {
+                    task.abort();
+                }
+                let task = spawn_reload(&request_handler, &config_rx.borrow_and_update());
+                reload_task = Some((Reload::Config, task));
+            }
+            result = async { (&mut reload_task.as_mut().unwrap().1).await }, if reload_task.is_some() => {
+                let Some((reason, _)) = reload_task.take() else {
+                    continue;
+                };
+                let result = result.map_err(anyhow::Error::from).and_then(|result| result);
+                match (reason, result) {
+                    (Reload::MissingInstances, Ok(handler)) if !handler.is_missing_instances => {
+                        info!("Retrieved the *arr instances from Seerr");
+                        request_handler = Arc::new(handler);
+                    }
+                    (Reload::MissingInstances, Ok(_)) => {}
+                    (Reload::MissingInstances, Err(e)) => {
+                        warn!("Could not retrieve the *arr instances: {e}")
+                    }
+                    (Reload::Config, Ok(handler)) => {
+                        info!("Applied the new configuration");
+                        // The events already queued finish with the previous handler
+                        request_handler = Arc::new(handler);
+                        if request_handler.sync_interval != scan_interval {
+                            scan_interval = request_handler.sync_interval;
+                            next_scan_requests = Instant::now() + scan_interval;
+                        }
+                        playback_interval = request_handler
+                            .media_server
+                            .as_ref()
+                            .map(|media_server| media_server.poll_interval());
+                    }
+                    (Reload::Config, Err(e)) => error!(
+                        "Could not apply the new configuration, keeping the current one: {e}"
+                    ),
+                }
+            }
             result = close_rx.changed() => {
                 debug!("Closing controller");
                 if result.is_ok() && *close_rx.borrow_and_update() {
-                    break Ok(());
+                    break;
                 }
             }
         };
     }
+
+    // The events already dispatched are processed and the open digests and held notifications
+    // sent before stopping
+    if let Some((_, task)) = reload_task {
+        task.abort();
+    }
+    drop(workers);
+    for task in worker_tasks {
+        if let Err(e) = task.await {
+            error!("Worker failed: {e}");
+        }
+    }
+    digest_tasks.join_all().await;
+    // The notifications held for the media server are queued too, without their link
+    if let Some(task) = playback_task
+        && let Err(e) = task.await
+    {
+        error!("Playback check failed: {e}");
+    }
+    request_handler.flush().await;
+    Ok(())
 }
@@ -0,0 +1,116 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use serde::Serialize;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use utoipa::ToSchema;
+
+/// Name of the Seerr upstream in the status
+pub const SEERR: &str = "seerr";
+
+/// Name of the media server upstream in the status
+pub const MEDIA_SERVER: &str = "media_server";
+
+/// Name of an *arr instance in the status, e.g. `sonarr/anime`
+pub fn instance_name(service: &str, name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("{service}/{name}"),
+        None => service.to_string(),
+    }
+}
+
+/// Result of the last check of a service informarr depends on
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct UpstreamStatus {
+    pub name: String,
+    pub healthy: bool,
+    /// Error of the last check if it failed
+    pub error: Option<String>,
+    pub checked_at: String,
+    /// Time of the check that changed the health of the upstream
+    pub since: String,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct HealthStatus {
+    /// Whether the requests are tracked, the webhooks received before are buffered
+    pub ready: bool,
+    /// Whether any upstream is failing
+    pub degraded: bool,
+    pub upstreams: Vec<UpstreamStatus>,
+}
+
+/// Health of the upstreams shared by the controller, which checks them, and the admin API
+#[derive(Default)]
+pub struct UpstreamHealth {
+    ready: AtomicBool,
+    upstreams: Mutex<BTreeMap<String, UpstreamStatus>>,
+}
+
+impl UpstreamHealth {
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::Relaxed);
+    }
+
+    /// Stores the result of a check of the upstream
+    pub fn record<T, E: Display>(&self, name: &str, result: &Result<T, E>) {
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_default();
+        let healthy = result.is_ok();
+        let error = result.as_ref().err().map(|e| e.to_string());
+
+        let mut upstreams = self.upstreams.lock().unwrap();
+        let since = match upstreams.get(name) {
+            Some(status) if status.healthy == healthy => status.since.clone(),
+            _ => now.clone(),
+        };
+        upstreams.insert(
+            name.to_string(),
+            UpstreamStatus {
+                name: name.to_string(),
+                healthy,
+                error,
+                checked_at: now,
+                since,
+            },
+        );
+    }
+
+    /// Forgets the upstreams removed from the configuration
+    pub fn retain(&self, names: &[String]) {
+        self.upstreams
+            .lock()
+            .unwrap()
+            .retain(|name, _| names.contains(name));
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.upstreams
+            .lock()
+            .unwrap()
+            .values()
+            .all(|status| status.healthy)
+    }
+
+    pub fn status(&self) -> HealthStatus {
+        let upstreams = self
+            .upstreams
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<UpstreamStatus>>();
+        HealthStatus {
+            ready: self.ready.load(Ordering::Relaxed),
+            degraded: upstreams.iter().any(|status| !status.healthy),
+            upstreams,
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use log::warn;
+
+const DEFAULT_LOCALE: &str = "en";
+
+/// Translations of the notification texts, the placeholders are written as `{name}`
+const BUNDLES: [(&str, &str); 2] = [
+    ("en", include_str!("../locales/en.yml")),
+    ("es", include_str!("../locales/es.yml")),
+];
+
+static TRANSLATIONS: LazyLock<HashMap<&'static str, HashMap<String, String>>> =
+    LazyLock::new(|| {
+        BUNDLES
+            .iter()
+            .map(|(locale, content)| {
+                let bundle = serde_yaml::from_str(content)
+                    .unwrap_or_else(|e| panic!("Invalid {locale} translations: {e}"));
+                (*locale, bundle)
+            })
+            .collect()
+    });
+
+#[derive(Debug, Clone, Copy)]
+pub struct Locale(&'static str);
+
+impl Locale {
+    /// Picks the first supported locale, regional variants like `es-MX` use their language
+    pub fn from_candidates<'a>(candidates: impl IntoIterator<Item = Option<&'a str>>) -> Self {
+        candidates
+            .into_iter()
+            .flatten()
+            .find_map(Self::find)
+            .unwrap_or(Self(DEFAULT_LOCALE))
+    }
+
+    fn find(code: &str) -> Option<Self> {
+        let code = code.trim().to_lowercase().replace('_', "-");
+        let language = code.split('-').next().unwrap_or_default();
+        BUNDLES
+            .iter()
+            .map(|(locale, _)| *locale)
+            .find(|locale| *locale == code || *locale == language)
+            .map(Self)
+    }
+
+    /// Translated text, falls back to English and then to the key itself
+    pub fn text(&self, key: &str) -> String {
+        let translation = [self.0, DEFAULT_LOCALE]
+            .iter()
+            .find_map(|locale| TRANSLATIONS.get(locale)?.get(key));
+        match translation {
+            Some(text) => text.clone(),
+            None => {
+                warn!("Missing translation for {key}");
+                key.to_string()
+            }
+        }
+    }
+
+    /// Translated text with its `{name}` placeholders replaced
+    pub fn format(&self, key: &str, args: &[(&str, &dyn std::fmt::Display)]) -> String {
+        args.iter().fold(self.text(key), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), &value.to_string())
+        })
+    }
+}
@@ -1,35 +1,46 @@
-use std::net::Ipv4Addr;
+use std::sync::Arc;
 
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 use axum::{
     body::Body,
     extract::Request,
     middleware::{self, Next},
     response::Response,
 };
+use clap::Parser;
 use log::{debug, error, trace};
 use serde::{Deserialize, Serialize};
-use tokio::{
-    fs,
-    net::TcpListener,
-    signal,
-    sync::{mpsc, watch},
-};
+use tokio::{net::TcpListener, signal, sync::watch};
 use utoipa::{OpenApi, ToSchema};
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_swagger_ui::SwaggerUi;
 
 use crate::{
-    config::AppConfig,
+    admin::AdminApi,
+    cache::MetadataCache,
+    cli::{Cli, Command, ServeArgs},
+    health::UpstreamHealth,
+    reload::LoadedConfig,
     webhooks::{
-        WebhookListener, radarr::RadarrWebhook, seerr::SeerrWebhook, sonarr::SonarrWebhook,
+        EventQueueStats, WebhookListener, lidarr::LidarrWebhook, mediaserver::MediaServerWebhook,
+        radarr::RadarrWebhook, readarr::ReadarrWebhook, seerr::SeerrWebhook, sonarr::SonarrWebhook,
     },
 };
 
+mod admin;
+mod cache;
+mod cli;
+mod colors;
 mod config;
 mod controller;
+mod health;
+mod i18n;
+mod mediaserver;
 mod models;
+mod notify;
+mod reload;
 mod schema;
+mod templates;
 mod webhooks;
 
 #[derive(OpenApi)]
@@ -37,11 +48,17 @@ mod webhooks;
     tags(
         (name = webhooks::sonarr::TAG, description = "Sonarr API endpoints"),
         (name = webhooks::radarr::TAG, description = "Radarr API endpoints"),
-        (name = webhooks::seerr::TAG, description = "Seerr API endpoints")
+        (name = webhooks::lidarr::TAG, description = "Lidarr API endpoints"),
+        (name = webhooks::readarr::TAG, description = "Readarr API endpoints"),
+        (name = webhooks::seerr::TAG, description = "Seerr API endpoints"),
+        (name = webhooks::mediaserver::TAG, description = "Jellyfin, Emby and Plex API endpoints"),
+        (name = admin::TAG, description = "Administration endpoints")
     )
 )]
 struct ApiDoc;
 
+const ADMIN_PATH: &str = "/api/v1/admin";
+
 #[derive(Serialize, Deserialize, ToSchema, Debug)]
 struct MessageResponse {
     message: String,
@@ -108,71 +125,173 @@ async fn shutdown_signal(close_tx: watch::Sender<bool>, mut close_rx: watch::Rec
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    edolib::log::setup("informarr").await?;
+/// Webhooks of every service, the events they receive are forwarded to their listeners
+struct Webhooks {
+    sonarr: Arc<SonarrWebhook>,
+    radarr: Arc<RadarrWebhook>,
+    lidarr: Arc<LidarrWebhook>,
+    readarr: Arc<ReadarrWebhook>,
+    seerr: Arc<SeerrWebhook>,
+    media_server: Arc<MediaServerWebhook>,
+}
 
-    let port = std::env::var("INFORMARR_PORT")
-        .ok()
-        .and_then(|v| v.parse::<u16>().ok())
-        .unwrap_or(7532);
+impl Webhooks {
+    fn new() -> Self {
+        Self {
+            sonarr: SonarrWebhook::new(),
+            radarr: RadarrWebhook::new(),
+            lidarr: LidarrWebhook::new(),
+            readarr: ReadarrWebhook::new(),
+            seerr: SeerrWebhook::new(),
+            media_server: MediaServerWebhook::new(),
+        }
+    }
 
-    let content = match fs::read_to_string("config.yml").await {
-        Ok(content) => content,
-        Err(_) => fs::read_to_string("config.yaml")
-            .await
-            .map_err(|_e| anyhow!("Could not find config.yml or config.yaml files"))?,
-    };
+    fn router(&self) -> OpenApiRouter {
+        OpenApiRouter::with_openapi(ApiDoc::openapi())
+            .nest("/api/v1/sonarr", self.sonarr.clone().router())
+            .nest("/api/v1/radarr", self.radarr.clone().router())
+            .nest("/api/v1/lidarr", self.lidarr.clone().router())
+            .nest("/api/v1/readarr", self.readarr.clone().router())
+            .nest("/api/v1/seerr", self.seerr.clone().router())
+            .nest("/api/v1/mediaserver", self.media_server.clone().router())
+    }
 
-    let app_config = serde_yaml::from_str::<AppConfig>(&content)
-        .map_err(|e| anyhow!("Could not parse config: {e}"))?;
+    /// Depth of the event queue of every webhook
+    fn queue_stats(&self) -> Vec<(&'static str, EventQueueStats)> {
+        vec![
+            (webhooks::sonarr::TAG, self.sonarr.queue_stats()),
+            (webhooks::radarr::TAG, self.radarr.queue_stats()),
+            (webhooks::lidarr::TAG, self.lidarr.queue_stats()),
+            (webhooks::readarr::TAG, self.readarr.queue_stats()),
+            (webhooks::seerr::TAG, self.seerr.queue_stats()),
+            (webhooks::mediaserver::TAG, self.media_server.queue_stats()),
+        ]
+    }
+}
+
+async fn serve(cli: &Cli, args: &ServeArgs) -> Result<()> {
+    let config = LoadedConfig::load(cli.config.as_deref()).await?;
+    let queue = cli::open_queue(&config.app_config).await?;
+    let (config_tx, config_rx) = watch::channel(Arc::new(config));
 
-    let (sonarr_tx, sonarr_rx) = mpsc::unbounded_channel();
-    let (radarr_tx, radarr_rx) = mpsc::unbounded_channel();
-    let (seerr_tx, seerr_rx) = mpsc::unbounded_channel();
     let (close_tx, close_rx) = watch::channel(false);
 
-    let sonarr_webhook = SonarrWebhook::new();
-    sonarr_webhook.add_listener(move |event| {
-        let _ = sonarr_tx.send(event);
-    });
+    let capacity = config_rx.borrow().app_config.webhooks.queue_capacity;
+    let webhooks = Arc::new(Webhooks::new());
+    let sonarr_rx = webhooks.sonarr.subscribe(capacity);
+    let radarr_rx = webhooks.radarr.subscribe(capacity);
+    let lidarr_rx = webhooks.lidarr.subscribe(capacity);
+    let readarr_rx = webhooks.readarr.subscribe(capacity);
+    let seerr_rx = webhooks.seerr.subscribe(capacity);
+    let media_server_rx = webhooks.media_server.subscribe(capacity);
 
-    let radarr_webhook = RadarrWebhook::new();
-    radarr_webhook.add_listener(move |event| {
-        let _ = radarr_tx.send(event);
-    });
+    let reloader = tokio::spawn(reload::run(cli.config.clone(), config_tx, close_rx.clone()));
 
-    let seerr_webhook = SeerrWebhook::new();
-    seerr_webhook.add_listener(move |event| {
-        let _ = seerr_tx.send(event);
-    });
+    let sender = tokio::spawn(notify::run(
+        queue.clone(),
+        config_rx.clone(),
+        close_rx.clone(),
+    ));
+
+    let health = Arc::new(UpstreamHealth::default());
+    let cache = Arc::new(MetadataCache::new(&config_rx.borrow().app_config.seerr));
+    let admin_api = AdminApi::new(
+        queue.clone(),
+        health.clone(),
+        webhooks.clone(),
+        cache.clone(),
+    );
 
     let worker = tokio::spawn(controller::run(
-        app_config,
+        config_rx,
         sonarr_rx,
         radarr_rx,
+        lidarr_rx,
+        readarr_rx,
         seerr_rx,
-        close_tx.clone(),
+        media_server_rx,
+        queue,
+        health,
+        cache,
         close_rx.clone(),
     ));
 
-    let (router, api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
-        .nest("/api/v1/sonarr", sonarr_webhook.router())
-        .nest("/api/v1/radarr", radarr_webhook.router())
-        .nest("/api/v1/seerr", seerr_webhook.router())
+    let (router, api) = webhooks
+        .router()
         .layer(middleware::from_fn(logging_middleware))
         .split_for_parts();
 
     let router = router.merge(SwaggerUi::new("/").url("/apidoc/openapi.json", api));
 
-    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).await?;
+    let (admin_router, _) = OpenApiRouter::new()
+        .nest(ADMIN_PATH, admin_api.router())
+        .layer(middleware::from_fn(logging_middleware))
+        .split_for_parts();
+
+    let admin_listener = TcpListener::bind((args.admin_bind, args.admin_port)).await?;
+    let mut admin_close_rx = close_rx.clone();
+    let admin_server = tokio::spawn(async move {
+        axum::serve(admin_listener, admin_router)
+            .with_graceful_shutdown(async move {
+                let _ = admin_close_rx.wait_for(|close| *close).await;
+            })
+            .await
+    });
+
+    let listener = TcpListener::bind((args.bind, args.port)).await?;
     axum::serve(listener, router)
         .with_graceful_shutdown(shutdown_signal(close_tx, close_rx))
         .await?;
 
+    if let Err(err) = admin_server.await? {
+        error!("Error on admin API: {err}");
+    }
+
     if let Err(err) = worker.await? {
         error!("Error on spawned task: {err}");
     }
 
+    if let Err(err) = sender.await? {
+        error!("Error on notification sender: {err}");
+    }
+
+    if let Err(err) = reloader.await? {
+        error!("Error on configuration watcher: {err}");
+    }
+
     Ok(())
 }
+
+fn print_openapi() -> Result<()> {
+    let (_, api) = Webhooks::new().router().split_for_parts();
+    let api = api.nest(ADMIN_PATH, AdminApi::openapi());
+    println!("{}", api.to_pretty_json()?);
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    // The specifications are printed before the logger is set up to keep the output clean
+    match &cli.command {
+        Some(Command::Openapi) => return print_openapi(),
+        Some(Command::ConfigSchema) => return cli::print_config_schema(),
+        _ => {}
+    }
+
+    edolib::log::setup("informarr").await?;
+
+    match &cli.command {
+        None => serve(&cli, &ServeArgs::parse_from(["informarr"])).await,
+        Some(Command::Serve(args)) => serve(&cli, args).await,
+        Some(Command::CheckConfig) => cli::check_config(&cli).await,
+        Some(Command::ListRequests) => cli::list_requests(&cli).await,
+        Some(Command::SendTest) => cli::send_test(&cli).await,
+        Some(Command::Replay(args)) => cli::replay(&cli, args).await,
+        Some(Command::Redrive { id }) => cli::redrive(&cli, id.as_deref()).await,
+        Some(Command::Openapi) => print_openapi(),
+        Some(Command::ConfigSchema) => cli::print_config_schema(),
+    }
+}
@@ -0,0 +1,282 @@
+use std::sync::RwLock;
+
+use anyhow::{Context, Result, anyhow};
+use log::debug;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::config::{MediaServerConfig, MediaServerKind};
+
+/// Item that is expected to appear in the media server library
+#[derive(Debug, Clone)]
+pub struct MediaItemQuery {
+    pub tmdb_id: i32,
+    pub tvdb_id: Option<i32>,
+    pub is_show: bool,
+    pub season_number: Option<i32>,
+    pub episode_number: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct JellyfinItem {
+    id: String,
+    parent_index_number: Option<i32>,
+    index_number: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct JellyfinItems {
+    items: Vec<JellyfinItem>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlexMetadata {
+    rating_key: String,
+    parent_index: Option<i32>,
+    index: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlexContainer {
+    machine_identifier: Option<String>,
+    #[serde(rename = "Metadata", default)]
+    metadata: Vec<PlexMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlexResponse {
+    #[serde(rename = "MediaContainer")]
+    media_container: PlexContainer,
+}
+
+/// Client used to confirm that the media has been scanned by Jellyfin, Emby or Plex
+pub struct MediaServerClient {
+    config: MediaServerConfig,
+    client: reqwest::Client,
+    /// Retrieved by the first successful check, the links need it
+    server_id: RwLock<Option<String>>,
+}
+
+impl MediaServerClient {
+    pub fn new(config: MediaServerConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            server_id: RwLock::new(None),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self.config.kind {
+            MediaServerKind::Jellyfin => "Jellyfin",
+            MediaServerKind::Emby => "Emby",
+            MediaServerKind::Plex => "Plex",
+        }
+    }
+
+    pub fn poll_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.config.poll_interval)
+    }
+
+    pub fn max_wait(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.config.max_wait * 60)
+    }
+
+    /// Link to open the item in the media server web client, none until the server id is known
+    pub fn play_url(&self, item_id: &str) -> Option<Url> {
+        let server_id = self.server_id.read().unwrap().clone()?;
+        let base_url = self
+            .config
+            .public_url
+            .as_deref()
+            .unwrap_or(&self.config.url)
+            .trim_end_matches('/');
+        let url = match self.config.kind {
+            MediaServerKind::Jellyfin => format!(
+                "{base_url}/web/index.html#!/details?id={item_id}&serverId={}",
+                server_id
+            ),
+            MediaServerKind::Emby => format!(
+                "{base_url}/web/index.html#!/item?id={item_id}&serverId={}",
+                server_id
+            ),
+            MediaServerKind::Plex => format!(
+                "https://app.plex.tv/desktop#!/server/{}/details?key=%2Flibrary%2Fmetadata%2F{item_id}",
+                server_id
+            ),
+        };
+        Url::parse(&url).ok()
+    }
+
+    /// Searches the item in the media server library returning its id if found
+    pub async fn find_item(&self, query: &MediaItemQuery) -> Result<Option<String>> {
+        match self.config.kind {
+            MediaServerKind::Jellyfin | MediaServerKind::Emby => {
+                self.find_jellyfin_item(query).await
+            }
+            MediaServerKind::Plex => self.find_plex_item(query).await,
+        }
+    }
+
+    /// Checks that the server answers, storing its id
+    pub async fn check(&self) -> Result<()> {
+        let server_id = self
+            .get_server_id()
+            .await
+            .map_err(|e| anyhow!("Could not retrieve {} server id: {e}", self.name()))?;
+        debug!("{} server id: {server_id}", self.name());
+        *self.server_id.write().unwrap() = Some(server_id);
+        Ok(())
+    }
+
+    async fn get_server_id(&self) -> Result<String> {
+        match self.config.kind {
+            MediaServerKind::Jellyfin | MediaServerKind::Emby => {
+                let info = self.get_json::<Value>("/System/Info/Public", &[]).await?;
+                info.get("Id")
+                    .and_then(|id| id.as_str())
+                    .map(|id| id.to_string())
+                    .context("Server id not present")
+            }
+            MediaServerKind::Plex => self
+                .get_json::<PlexResponse>("/identity", &[])
+                .await?
+                .media_container
+                .machine_identifier
+                .context("Machine identifier not present"),
+        }
+    }
+
+    async fn find_jellyfin_item(&self, query: &MediaItemQuery) -> Result<Option<String>> {
+        let mut provider_ids = vec![format!("Tmdb.{}", query.tmdb_id)];
+        if let Some(tvdb_id) = query.tvdb_id {
+            provider_ids.push(format!("Tvdb.{tvdb_id}"));
+        }
+
+        let items = self
+            .get_json::<JellyfinItems>(
+                "/Items",
+                &[
+                    ("Recursive", "true".to_string()),
+                    (
+                        "IncludeItemTypes",
+                        if query.is_show { "Series" } else { "Movie" }.to_string(),
+                    ),
+                    ("AnyProviderIdEquals", provider_ids.join(",")),
+                ],
+            )
+            .await?
+            .items;
+
+        let Some(item) = items.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let Some(season_number) = query.season_number else {
+            return Ok(Some(item.id));
+        };
+
+        let episodes = self
+            .get_json::<JellyfinItems>(
+                &format!("/Shows/{}/Episodes", item.id),
+                &[("Season", season_number.to_string())],
+            )
+            .await?
+            .items;
+
+        Ok(episodes
+            .into_iter()
+            .find(|episode| {
+                episode.parent_index_number == Some(season_number)
+                    && (query.episode_number.is_none()
+                        || episode.index_number == query.episode_number)
+            })
+            .map(|episode| episode.id))
+    }
+
+    async fn find_plex_item(&self, query: &MediaItemQuery) -> Result<Option<String>> {
+        let mut guids = vec![format!("tmdb://{}", query.tmdb_id)];
+        if let Some(tvdb_id) = query.tvdb_id {
+            guids.push(format!("tvdb://{tvdb_id}"));
+        }
+
+        let mut item = None;
+        for guid in guids {
+            let items = self
+                .get_json::<PlexResponse>(
+                    "/library/all",
+                    &[
+                        ("guid", guid),
+                        ("type", if query.is_show { "2" } else { "1" }.to_string()),
+                    ],
+                )
+                .await?
+                .media_container
+                .metadata;
+            if let Some(found) = items.into_iter().next() {
+                item = Some(found);
+                break;
+            }
+        }
+
+        let Some(item) = item else {
+            return Ok(None);
+        };
+
+        let Some(season_number) = query.season_number else {
+            return Ok(Some(item.rating_key));
+        };
+
+        let episodes = self
+            .get_json::<PlexResponse>(
+                &format!("/library/metadata/{}/allLeaves", item.rating_key),
+                &[],
+            )
+            .await?
+            .media_container
+            .metadata;
+
+        Ok(episodes
+            .into_iter()
+            .find(|episode| {
+                episode.parent_index == Some(season_number)
+                    && (query.episode_number.is_none() || episode.index == query.episode_number)
+            })
+            .map(|episode| episode.rating_key))
+    }
+
+    async fn get_json<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<T> {
+        let url = format!("{}{path}", self.config.url.trim_end_matches('/'));
+        let request = self.client.get(&url).query(query);
+        let request = match self.config.kind {
+            MediaServerKind::Jellyfin | MediaServerKind::Emby => {
+                request.header("X-Emby-Token", &self.config.api_key)
+            }
+            MediaServerKind::Plex => request
+                .header("X-Plex-Token", &self.config.api_key)
+                .header("Accept", "application/json"),
+        };
+
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("GET {path} failed"))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("GET {path} response status: {}", response.status()));
+        }
+
+        response
+            .json::<T>()
+            .await
+            .with_context(|| format!("Could not parse {path} response"))
+    }
+}
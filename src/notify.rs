@@ -0,0 +1,516 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tokio::{
+    fs,
+    sync::{Mutex, Notify, watch},
+};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{DiscordConfig, QueueConfig, TelegramConfig},
+    reload::LoadedConfig,
+};
+
+mod discord;
+mod telegram;
+
+/// Longest time between two retries of the same notification
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Destination {
+    Discord,
+    Telegram,
+}
+
+/// Request rendered for a destination, ready to be sent
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+#[serde(tag = "destination", rename_all = "lowercase")]
+pub enum OutboundMessage {
+    Discord {
+        #[schema(value_type = Object)]
+        message: Value,
+    },
+    Telegram {
+        params: BTreeMap<String, String>,
+        is_photo: bool,
+    },
+}
+
+impl OutboundMessage {
+    pub fn destination(&self) -> Destination {
+        match self {
+            Self::Discord { .. } => Destination::Discord,
+            Self::Telegram { .. } => Destination::Telegram,
+        }
+    }
+}
+
+/// Message already delivered that can be edited later
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct SentMessage {
+    pub season_number: Option<i32>,
+    pub message_id: String,
+    pub is_photo: bool,
+}
+
+/// The message to edit was deleted, a new one is sent instead
+#[derive(Debug, thiserror::Error)]
+#[error("The message to edit no longer exists")]
+pub struct MessageGone;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct OutboundNotification {
+    pub message: OutboundMessage,
+    /// Key under which the sent message is remembered, the message sent before for the key and
+    /// the same season is edited instead of sending a new one
+    pub ongoing_key: Option<String>,
+    pub season_number: Option<i32>,
+}
+
+/// Notification stored in the queue until it is delivered or dead-lettered
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct QueuedNotification {
+    pub id: String,
+    pub created_at: String,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    /// Unix timestamp before which the notification is not retried
+    pub next_attempt_at: Option<i64>,
+    pub notification: OutboundNotification,
+}
+
+/// Notifications persisted on disk, the pending ones are sent by the worker and the ones that
+/// failed too many times are kept apart until they are re-driven
+pub struct NotificationQueue {
+    config: QueueConfig,
+    pending_dir: PathBuf,
+    dead_dir: PathBuf,
+    counter: AtomicU64,
+    notify: Notify,
+    /// Messages sent for each ongoing key, persisted so they are still edited after a restart
+    ongoing_messages: Mutex<HashMap<String, SentMessage>>,
+    ongoing_path: PathBuf,
+    /// Pending notifications pushed by this process, only tracked by the commands running next
+    /// to the server since they must not send the server ones
+    pushed_ids: Mutex<Option<HashSet<String>>>,
+}
+
+impl NotificationQueue {
+    pub async fn new(config: QueueConfig) -> Result<Self> {
+        let path = PathBuf::from(&config.path);
+        let pending_dir = path.join("pending");
+        let dead_dir = path.join("dead");
+        for dir in [&pending_dir, &dead_dir] {
+            fs::create_dir_all(dir)
+                .await
+                .with_context(|| format!("Could not create queue directory {}", dir.display()))?;
+        }
+
+        let ongoing_path = path.join("ongoing.json");
+        let ongoing_messages = match fs::read_to_string(&ongoing_path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring invalid {}: {e}", ongoing_path.display());
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+
+        Ok(Self {
+            config,
+            pending_dir,
+            dead_dir,
+            counter: AtomicU64::new(0),
+            notify: Notify::new(),
+            ongoing_messages: Mutex::new(ongoing_messages),
+            ongoing_path,
+            pushed_ids: Mutex::new(None),
+        })
+    }
+
+    pub async fn push(&self, notification: OutboundNotification) -> Result<()> {
+        let now = OffsetDateTime::now_utc();
+        let counter = self.counter.fetch_add(1, Ordering::Relaxed) % 10000;
+        let item = QueuedNotification {
+            // Sortable by creation so the notifications are sent in order
+            id: format!("{:020}-{counter:04}", now.unix_timestamp_nanos()),
+            created_at: now.format(&Rfc3339).unwrap_or_default(),
+            attempts: 0,
+            last_error: None,
+            next_attempt_at: None,
+            notification,
+        };
+        write_item(&self.pending_dir, &item).await?;
+        debug!("Queued notification {}", item.id);
+        if let Some(pushed_ids) = self.pushed_ids.lock().await.as_mut() {
+            pushed_ids.insert(item.id);
+        }
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    pub async fn pending(&self) -> Result<Vec<QueuedNotification>> {
+        read_items(&self.pending_dir).await
+    }
+
+    /// Starts tracking the notifications pushed by this process, so [`flush`] only sends them
+    pub async fn track_pushed(&self) {
+        self.pushed_ids.lock().await.get_or_insert_default();
+    }
+
+    /// Pending notifications pushed by this process
+    async fn pushed_pending(&self) -> Result<Vec<QueuedNotification>> {
+        let items = self.pending().await?;
+        let pushed_ids = self.pushed_ids.lock().await;
+        Ok(items
+            .into_iter()
+            .filter(|item| {
+                pushed_ids
+                    .as_ref()
+                    .is_some_and(|ids| ids.contains(&item.id))
+            })
+            .collect())
+    }
+
+    /// Stops tracking the notification once it leaves the pending ones
+    async fn forget_pushed(&self, id: &str) {
+        if let Some(pushed_ids) = self.pushed_ids.lock().await.as_mut() {
+            pushed_ids.remove(id);
+        }
+    }
+
+    pub async fn dead_letters(&self) -> Result<Vec<QueuedNotification>> {
+        read_items(&self.dead_dir).await
+    }
+
+    /// Moves the dead-lettered notification back to the queue, returns false if not found
+    pub async fn redrive(&self, id: &str) -> Result<bool> {
+        if !is_valid_id(id) {
+            return Ok(false);
+        }
+        let path = self.dead_dir.join(format!("{id}.json"));
+        if !fs::try_exists(&path).await.unwrap_or(false) {
+            return Ok(false);
+        }
+
+        let mut item = read_item(&path).await?;
+        item.attempts = 0;
+        item.last_error = None;
+        item.next_attempt_at = None;
+        write_item(&self.pending_dir, &item).await?;
+        fs::remove_file(&path).await?;
+        info!("Re-driving notification {id}");
+        self.notify.notify_one();
+        Ok(true)
+    }
+
+    pub async fn redrive_all(&self) -> Result<usize> {
+        let mut count = 0;
+        for item in self.dead_letters().await? {
+            if self.redrive(&item.id).await? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Message previously sent for the key, used to edit it instead of sending a new one
+    async fn ongoing_message(&self, key: &str) -> Option<SentMessage> {
+        self.ongoing_messages.lock().await.get(key).cloned()
+    }
+
+    async fn complete(&self, item: &QueuedNotification, sent: Option<SentMessage>) -> Result<()> {
+        if let (Some(key), Some(sent)) = (&item.notification.ongoing_key, sent) {
+            let mut ongoing_messages = self.ongoing_messages.lock().await;
+            ongoing_messages.insert(key.clone(), sent);
+            write_json(&self.ongoing_path, &*ongoing_messages).await?;
+        }
+        fs::remove_file(self.pending_dir.join(format!("{}.json", item.id))).await?;
+        self.forget_pushed(&item.id).await;
+        Ok(())
+    }
+
+    async fn fail(&self, mut item: QueuedNotification, error: String) -> Result<()> {
+        item.attempts += 1;
+        item.last_error = Some(error);
+
+        if item.attempts >= self.config.max_attempts {
+            error!(
+                "Notification {} failed {} times, moving it to the dead letters",
+                item.id, item.attempts
+            );
+            write_item(&self.dead_dir, &item).await?;
+            fs::remove_file(self.pending_dir.join(format!("{}.json", item.id))).await?;
+            self.forget_pushed(&item.id).await;
+            return Ok(());
+        }
+
+        let delay = self.retry_delay(item.attempts);
+        item.next_attempt_at =
+            Some(OffsetDateTime::now_utc().unix_timestamp() + delay.as_secs() as i64);
+        write_item(&self.pending_dir, &item).await
+    }
+
+    fn retry_delay(&self, attempts: u32) -> Duration {
+        Duration::from_secs(self.config.retry_interval)
+            .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+            .min(MAX_RETRY_DELAY)
+    }
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_digit() || c == '-')
+}
+
+async fn read_item(path: &Path) -> Result<QueuedNotification> {
+    let content = fs::read_to_string(path).await?;
+    serde_json::from_str(&content).with_context(|| format!("Invalid queue file {}", path.display()))
+}
+
+async fn read_items(dir: &Path) -> Result<Vec<QueuedNotification>> {
+    let mut items = Vec::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        match read_item(&path).await {
+            Ok(item) => items.push(item),
+            Err(e) => warn!("Skipping queued notification: {e}"),
+        }
+    }
+    items.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(items)
+}
+
+async fn write_item(dir: &Path, item: &QueuedNotification) -> Result<()> {
+    write_json(&dir.join(format!("{}.json", item.id)), item).await
+}
+
+/// Writes the file next to its destination first so a crash never leaves it half written
+async fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_vec_pretty(value)?).await?;
+    fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
+struct NotificationSender {
+    queue: Arc<NotificationQueue>,
+    discord: Option<discord::DiscordSender>,
+    telegram: Option<telegram::TelegramSender>,
+    /// Webhook URL and bot token, removed from the errors stored in the queue since they are
+    /// served by the admin API
+    secrets: Vec<String>,
+}
+
+impl NotificationSender {
+    fn new(
+        queue: Arc<NotificationQueue>,
+        client: reqwest::Client,
+        rate_limits: Arc<discord::DiscordRateLimits>,
+        discord: Option<DiscordConfig>,
+        telegram: Option<TelegramConfig>,
+    ) -> Self {
+        let secrets = discord
+            .as_ref()
+            .map(|config| config.webhook_url.clone())
+            .into_iter()
+            .chain(telegram.as_ref().map(|config| config.bot_token.clone()))
+            .filter(|secret| !secret.is_empty())
+            .collect();
+        Self {
+            queue,
+            secrets,
+            discord: discord
+                .map(|config| discord::DiscordSender::new(config, client.clone(), rate_limits)),
+            telegram: telegram.map(|config| telegram::TelegramSender::new(config, client)),
+        }
+    }
+
+    /// Sends the due notifications, returns the time until the next retry if any
+    async fn process_pending(&self) -> Option<Duration> {
+        match self.queue.pending().await {
+            Ok(items) => self.process(items).await,
+            Err(e) => {
+                error!("Could not read the notification queue: {e}");
+                Some(Duration::from_secs(self.queue.config.retry_interval))
+            }
+        }
+    }
+
+    /// Sends the due notifications among the items, returns the time until the next retry if any
+    async fn process(&self, items: Vec<QueuedNotification>) -> Option<Duration> {
+        let mut next_retry: Option<i64> = None;
+        for item in items {
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            if let Some(next_attempt_at) = item.next_attempt_at.filter(|at| *at > now) {
+                next_retry = Some(next_retry.map_or(next_attempt_at, |v| v.min(next_attempt_at)));
+                continue;
+            }
+
+            let result = match self.deliver(&item.notification).await {
+                Ok(sent) => self.queue.complete(&item, sent).await,
+                Err(e) => {
+                    let error = self.redact(e.to_string());
+                    warn!(
+                        "Failed sending {:?} notification {}: {error}",
+                        item.notification.message.destination(),
+                        item.id
+                    );
+                    let retry_at = self.queue.retry_delay(item.attempts + 1).as_secs() as i64;
+                    next_retry = Some(next_retry.map_or(now + retry_at, |v| v.min(now + retry_at)));
+                    self.queue.fail(item, error).await
+                }
+            };
+            if let Err(e) = result {
+                error!("Could not update the notification queue: {e}");
+            }
+        }
+
+        next_retry.map(|at| {
+            Duration::from_secs((at - OffsetDateTime::now_utc().unix_timestamp()).max(1) as u64)
+        })
+    }
+
+    fn redact(&self, error: String) -> String {
+        self.secrets
+            .iter()
+            .fold(error, |error, secret| error.replace(secret, "[redacted]"))
+    }
+
+    async fn deliver(&self, notification: &OutboundNotification) -> Result<Option<SentMessage>> {
+        // Looked up when sending, so the ones queued before the first message is sent edit it
+        let edit = match &notification.ongoing_key {
+            Some(key) => self
+                .queue
+                .ongoing_message(key)
+                .await
+                .filter(|message| message.season_number == notification.season_number),
+            None => None,
+        };
+        let edit = edit.as_ref();
+        let mut result = self.send(&notification.message, edit).await;
+        // The other errors are retried by the queue, sending a new message would duplicate it
+        if edit.is_some() && result.as_ref().is_err_and(|e| e.is::<MessageGone>()) {
+            warn!("The previous message was deleted, sending a new one");
+            result = self.send(&notification.message, None).await;
+        }
+
+        Ok(result?.map(|mut sent| {
+            sent.season_number = notification.season_number;
+            sent
+        }))
+    }
+
+    async fn send(
+        &self,
+        message: &OutboundMessage,
+        edit: Option<&SentMessage>,
+    ) -> Result<Option<SentMessage>> {
+        match message {
+            OutboundMessage::Discord { message } => {
+                let discord = self
+                    .discord
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("Discord is not configured"))?;
+                discord.send(message, edit).await
+            }
+            OutboundMessage::Telegram { params, is_photo } => {
+                let telegram = self
+                    .telegram
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("Telegram is not configured"))?;
+                telegram.send(params, *is_photo, edit).await
+            }
+        }
+    }
+}
+
+/// Background worker delivering the queued notifications, the destinations are replaced when
+/// the configuration is reloaded
+///
+/// The HTTP client and the Discord rate limits outlive the reloads, so the limits learned
+/// before still apply to the next messages.
+pub async fn run(
+    queue: Arc<NotificationQueue>,
+    mut config_rx: watch::Receiver<Arc<LoadedConfig>>,
+    mut close_rx: watch::Receiver<bool>,
+) -> Result<()> {
+    let client = reqwest::Client::new();
+    let rate_limits = Arc::new(discord::DiscordRateLimits::default());
+    let new_sender = |config: Arc<LoadedConfig>| {
+        NotificationSender::new(
+            queue.clone(),
+            client.clone(),
+            rate_limits.clone(),
+            config.app_config.discord.clone(),
+            config.app_config.telegram.clone(),
+        )
+    };
+    let mut sender = new_sender(config_rx.borrow_and_update().clone());
+    let mut is_config_open = true;
+
+    let idle_interval = Duration::from_secs(queue.config.retry_interval);
+    loop {
+        let next_retry = sender.process_pending().await;
+        tokio::select! {
+            _ = queue.notify.notified() => {},
+            _ = tokio::time::sleep(next_retry.unwrap_or(idle_interval)) => {},
+            result = config_rx.changed(), if is_config_open => {
+                match result {
+                    Ok(()) => sender = new_sender(config_rx.borrow_and_update().clone()),
+                    Err(_) => is_config_open = false,
+                }
+            }
+            result = close_rx.changed() => {
+                debug!("Closing notification sender");
+                if result.is_ok() && *close_rx.borrow_and_update() {
+                    break Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Sends the notifications pushed by this process once, used by the commands that run without
+/// the server
+///
+/// The other pending notifications belong to the server, which may be running on the same queue.
+pub async fn flush(
+    queue: Arc<NotificationQueue>,
+    discord: Option<DiscordConfig>,
+    telegram: Option<TelegramConfig>,
+) -> Result<()> {
+    let sender = NotificationSender::new(
+        queue.clone(),
+        reqwest::Client::new(),
+        Arc::default(),
+        discord,
+        telegram,
+    );
+    sender.process(queue.pushed_pending().await?).await;
+
+    let pending = queue.pushed_pending().await?.len();
+    if pending > 0 {
+        return Err(anyhow!(
+            "{pending} notifications could not be sent, they stay queued until the server retries them"
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result, anyhow};
+use log::{debug, warn};
+use reqwest::Url;
+use serde_json::Value;
+use tokio::{sync::Mutex, time::Instant};
+
+use crate::{
+    config::DiscordConfig,
+    notify::{MessageGone, SentMessage},
+};
+
+const MAX_RETRIES: u32 = 5;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Rate limit state reported by Discord for a bucket
+#[derive(Debug, Default)]
+struct DiscordBucket {
+    remaining: Option<u64>,
+    reset_at: Option<Instant>,
+}
+
+impl DiscordBucket {
+    fn update(&mut self, headers: &reqwest::header::HeaderMap) {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        if let Some(remaining) = header("X-RateLimit-Remaining").and_then(|v| v.parse().ok()) {
+            self.remaining = Some(remaining);
+        }
+        if let Some(reset_after) = header("X-RateLimit-Reset-After")
+            .and_then(|v| v.parse::<f64>().ok())
+            .and_then(|v| Duration::try_from_secs_f64(v).ok())
+        {
+            self.reset_at = Some(Instant::now() + reset_after);
+        }
+    }
+
+    /// Waits until the bucket has requests left
+    async fn wait(&mut self) {
+        if let (Some(0), Some(reset_at)) = (self.remaining, self.reset_at) {
+            if reset_at > Instant::now() {
+                debug!("Discord rate limit reached, waiting for the bucket reset");
+                tokio::time::sleep_until(reset_at).await;
+            }
+            self.remaining = None;
+        }
+    }
+}
+
+/// Rate limit buckets of the Discord routes, locking one queues the requests of its routes
+#[derive(Debug, Default)]
+pub struct DiscordRateLimits {
+    /// Bucket reported by the `X-RateLimit-Bucket` header for each route
+    routes: Mutex<HashMap<String, String>>,
+    /// Buckets by id and webhook, a route has its own one until Discord tells which bucket it
+    /// belongs to
+    buckets: Mutex<HashMap<String, Arc<Mutex<DiscordBucket>>>>,
+}
+
+impl DiscordRateLimits {
+    async fn bucket(&self, route: &str) -> Arc<Mutex<DiscordBucket>> {
+        let id = self
+            .routes
+            .lock()
+            .await
+            .get(route)
+            .cloned()
+            .unwrap_or_else(|| route.to_string());
+        self.buckets.lock().await.entry(id).or_default().clone()
+    }
+
+    /// Remembers the bucket of the route, its state is kept if the bucket is not known yet
+    async fn assign(
+        &self,
+        route: &str,
+        webhook_url: &str,
+        headers: &reqwest::header::HeaderMap,
+        bucket: &Arc<Mutex<DiscordBucket>>,
+    ) {
+        let Some(id) = headers
+            .get("X-RateLimit-Bucket")
+            .and_then(|value| value.to_str().ok())
+        else {
+            return;
+        };
+        // The limits of a bucket apply to each webhook separately
+        let id = format!("{id} {webhook_url}");
+        let mut routes = self.routes.lock().await;
+        if routes.get(route) != Some(&id) {
+            routes.insert(route.to_string(), id.clone());
+            self.buckets
+                .lock()
+                .await
+                .entry(id)
+                .or_insert_with(|| bucket.clone());
+        }
+    }
+}
+
+pub struct DiscordSender {
+    config: DiscordConfig,
+    client: reqwest::Client,
+    rate_limits: Arc<DiscordRateLimits>,
+}
+
+impl DiscordSender {
+    pub fn new(
+        config: DiscordConfig,
+        client: reqwest::Client,
+        rate_limits: Arc<DiscordRateLimits>,
+    ) -> Self {
+        Self {
+            config,
+            client,
+            rate_limits,
+        }
+    }
+
+    /// Posts the message, or edits the given one, returning the message sent
+    pub async fn send(
+        &self,
+        message: &Value,
+        edit: Option<&SentMessage>,
+    ) -> Result<Option<SentMessage>> {
+        let message_id = self
+            .send_request(
+                message.clone(),
+                edit.map(|previous| previous.message_id.as_str()),
+            )
+            .await
+            .map_err(|e| {
+                if e.is::<MessageGone>() {
+                    e
+                } else {
+                    anyhow!("Failed sending Discord request: {e}")
+                }
+            })?;
+
+        Ok(message_id.map(|message_id| SentMessage {
+            season_number: None,
+            message_id,
+            is_photo: false,
+        }))
+    }
+
+    async fn send_request(
+        &self,
+        mut data: Value,
+        message_id: Option<&str>,
+    ) -> Result<Option<String>> {
+        let webhook_url = &self.config.webhook_url;
+        let mut url = Url::parse(webhook_url).context("Invalid webhook URL")?;
+
+        let method = match message_id {
+            Some(message_id) => {
+                url.path_segments_mut()
+                    .map_err(|_| anyhow!("Invalid webhook URL"))?
+                    .extend(["messages", message_id]);
+                // The webhook identity cannot be changed when editing
+                if let Some(object) = data.as_object_mut() {
+                    object.remove("username");
+                    object.remove("avatar_url");
+                }
+                reqwest::Method::PATCH
+            }
+            None => {
+                // Waiting makes Discord return the created message
+                url.query_pairs_mut().append_pair("wait", "true");
+                reqwest::Method::POST
+            }
+        };
+
+        let route = format!("{method} {webhook_url}");
+        let shared_bucket = self.rate_limits.bucket(&route).await;
+        let mut bucket = shared_bucket.lock().await;
+
+        let mut attempt = 0;
+        loop {
+            bucket.wait().await;
+
+            // Send the message via the Webhook URL
+            let delay = match self
+                .client
+                .request(method.clone(), url.clone())
+                .header("Content-Type", "application/json")
+                .json(&data)
+                .send()
+                .await
+            {
+                Ok(response) => {
+                    bucket.update(response.headers());
+                    self.rate_limits
+                        .assign(&route, webhook_url, response.headers(), &shared_bucket)
+                        .await;
+                    let status = response.status();
+                    if status.is_success() {
+                        let body = response.json::<Value>().await.unwrap_or_default();
+                        return Ok(body["id"].as_str().map(|id| id.to_string()));
+                    }
+
+                    if status == reqwest::StatusCode::NOT_FOUND && message_id.is_some() {
+                        return Err(MessageGone.into());
+                    }
+
+                    let retry_after_header = response
+                        .headers()
+                        .get("Retry-After")
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| value.parse::<f64>().ok());
+                    let body = response.json::<Value>().await.unwrap_or_default();
+
+                    let is_retryable = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                        || status.is_server_error();
+                    if !is_retryable || attempt >= MAX_RETRIES {
+                        return Err(anyhow!("Response status: {status} {body}"));
+                    }
+                    warn!("Discord webhook responded {status}, retrying");
+                    body["retry_after"]
+                        .as_f64()
+                        .or(retry_after_header)
+                        .and_then(|v| Duration::try_from_secs_f64(v).ok())
+                        .unwrap_or_else(|| RETRY_DELAY * 2u32.pow(attempt))
+                }
+                Err(e) => {
+                    // The URL holds the webhook token
+                    let e = e.without_url();
+                    if attempt >= MAX_RETRIES {
+                        return Err(anyhow!("Webhook request failed: {e}"));
+                    }
+                    warn!("Discord webhook request failed, retrying: {e}");
+                    RETRY_DELAY * 2u32.pow(attempt)
+                }
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
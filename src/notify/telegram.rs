@@ -0,0 +1,177 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use anyhow::{Result, anyhow};
+use log::warn;
+use serde_json::Value;
+
+use crate::{
+    config::TelegramConfig,
+    notify::{MessageGone, SentMessage},
+};
+
+const MAX_RETRIES: u32 = 4;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Error returned by the Telegram Bot API
+#[derive(Debug, thiserror::Error)]
+#[error("{status} {description}")]
+pub struct TelegramError {
+    status: reqwest::StatusCode,
+    description: String,
+    retry_after: Option<u64>,
+}
+
+pub struct TelegramSender {
+    config: TelegramConfig,
+    client: reqwest::Client,
+}
+
+impl TelegramSender {
+    pub fn new(config: TelegramConfig, client: reqwest::Client) -> Self {
+        Self { config, client }
+    }
+
+    /// Sends the message, or edits the given one, returning the message sent
+    pub async fn send(
+        &self,
+        params: &BTreeMap<String, String>,
+        is_photo: bool,
+        edit: Option<&SentMessage>,
+    ) -> Result<Option<SentMessage>> {
+        let sent = |message_id: Option<String>, is_photo: bool| {
+            message_id.map(|message_id| SentMessage {
+                season_number: None,
+                message_id,
+                is_photo,
+            })
+        };
+
+        // The photo of an existing message cannot be replaced, only its text
+        if let Some(previous) = edit {
+            let text_key = if previous.is_photo { "caption" } else { "text" };
+            let mut edit_params = params
+                .iter()
+                .filter(|(name, _)| matches!(name.as_str(), "chat_id" | "parse_mode"))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect::<BTreeMap<String, String>>();
+            edit_params.insert("message_id".to_string(), previous.message_id.clone());
+            edit_params.insert(
+                text_key.to_string(),
+                params
+                    .get("caption")
+                    .or(params.get("text"))
+                    .cloned()
+                    .unwrap_or_default(),
+            );
+
+            let method = if previous.is_photo {
+                "editMessageCaption"
+            } else {
+                "editMessageText"
+            };
+            return match self.send_request(&edit_params, method).await {
+                Ok(_) => Ok(Some(previous.clone())),
+                Err(e) => {
+                    let description = e
+                        .downcast_ref::<TelegramError>()
+                        .map(|error| error.description.as_str())
+                        .unwrap_or_default();
+                    // Editing with the same content is refused although the message is up to date
+                    if description.contains("message is not modified") {
+                        Ok(Some(previous.clone()))
+                    } else if description.contains("message to edit not found") {
+                        Err(MessageGone.into())
+                    } else {
+                        Err(e)
+                    }
+                }
+            };
+        }
+
+        if !is_photo {
+            let message_id = self.send_request(params, "sendMessage").await?;
+            return Ok(sent(message_id, false));
+        }
+
+        match self.send_request(params, "sendPhoto").await {
+            // Telegram may fail fetching the poster or reject a long caption
+            Err(e)
+                if e.downcast_ref::<TelegramError>()
+                    .is_some_and(|e| e.status == reqwest::StatusCode::BAD_REQUEST) =>
+            {
+                warn!("Could not send Telegram photo, sending the message without it: {e}");
+                let mut params = params.clone();
+                params.remove("photo");
+                if let Some(caption) = params.remove("caption") {
+                    params.insert("text".to_string(), caption);
+                }
+                let message_id = self.send_request(&params, "sendMessage").await?;
+                Ok(sent(message_id, false))
+            }
+            result => Ok(sent(result?, true)),
+        }
+    }
+
+    /// Calls the Bot API method returning the id of the message, rate limited and failed
+    /// requests are retried with backoff
+    async fn send_request(
+        &self,
+        params: &BTreeMap<String, String>,
+        method: &str,
+    ) -> Result<Option<String>> {
+        let url = format!(
+            "https://api.telegram.org/bot{}/{method}",
+            self.config.bot_token
+        );
+        let mut attempt = 0;
+        loop {
+            let form = params
+                .iter()
+                .fold(reqwest::multipart::Form::new(), |form, (name, value)| {
+                    form.text(name.clone(), value.clone())
+                });
+
+            // Send the POST request to the Telegram Bot API
+            let delay = match self.client.post(&url).multipart(form).send().await {
+                Ok(response) if response.status().is_success() => {
+                    let body = response.json::<Value>().await.unwrap_or_default();
+                    return Ok(body["result"]["message_id"]
+                        .as_i64()
+                        .map(|message_id| message_id.to_string()));
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let body = response.json::<Value>().await.unwrap_or_default();
+                    let error = TelegramError {
+                        status,
+                        description: body["description"].as_str().unwrap_or_default().to_string(),
+                        retry_after: body["parameters"]["retry_after"].as_u64(),
+                    };
+
+                    let is_retryable = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                        || status.is_server_error();
+                    if !is_retryable || attempt >= MAX_RETRIES {
+                        return Err(error.into());
+                    }
+                    warn!("Telegram {method} failed, retrying: {error}");
+                    error
+                        .retry_after
+                        .map(Duration::from_secs)
+                        .unwrap_or_else(|| RETRY_DELAY * 2u32.pow(attempt))
+                }
+                Err(e) => {
+                    // The URL holds the bot token
+                    let e = e.without_url();
+                    if attempt >= MAX_RETRIES {
+                        return Err(anyhow!("POST failed: {e}"));
+                    }
+                    warn!("Telegram {method} request failed, retrying: {e}");
+                    RETRY_DELAY * 2u32.pow(attempt)
+                }
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
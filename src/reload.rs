@@ -0,0 +1,101 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Result, anyhow};
+use log::{debug, error, info};
+use tokio::{fs, sync::watch};
+
+use crate::{config::AppConfig, templates::MessageTemplates};
+
+/// Time between two checks of the configuration file
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Time given to the editors to finish writing the file before reading it
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+/// Configuration checked completely, including the parts only validated when they are used
+pub struct LoadedConfig {
+    pub app_config: AppConfig,
+    pub templates: MessageTemplates,
+}
+
+impl LoadedConfig {
+    pub async fn load(path: Option<&Path>) -> Result<Self> {
+        let app_config = AppConfig::load(path).await?;
+        let templates = MessageTemplates::new(&app_config)
+            .map_err(|e| anyhow!("Invalid message templates: {e}"))?;
+        Ok(Self {
+            app_config,
+            templates,
+        })
+    }
+}
+
+async fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).await.ok()?.modified().ok()
+}
+
+async fn reload(path: Option<&Path>, config_tx: &watch::Sender<Arc<LoadedConfig>>) {
+    match LoadedConfig::load(path).await {
+        Ok(config) => {
+            config_tx.send_replace(Arc::new(config));
+            info!("Configuration reloaded");
+        }
+        Err(e) => error!("Invalid configuration, keeping the current one: {e}"),
+    }
+}
+
+/// Reloads the configuration when its file changes or the process receives a SIGHUP, the
+/// new configuration is only published if it is valid
+///
+/// The server address, the queue, the webhook queue capacity, the number of workers and the
+/// cache TTL are only read on startup.
+pub async fn run(
+    path: Option<PathBuf>,
+    config_tx: watch::Sender<Arc<LoadedConfig>>,
+    mut close_rx: watch::Receiver<bool>,
+) -> Result<()> {
+    let watched_file = AppConfig::find_file(path.as_deref());
+    let mut last_modified = match &watched_file {
+        Some(file) => modified_time(file).await,
+        None => None,
+    };
+
+    #[cfg(unix)]
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+
+    loop {
+        #[cfg(unix)]
+        let hangup_received = hangup.recv();
+        #[cfg(not(unix))]
+        let hangup_received = std::future::pending::<Option<()>>();
+
+        tokio::select! {
+            _ = hangup_received => {
+                info!("Received SIGHUP, reloading the configuration");
+                reload(path.as_deref(), &config_tx).await;
+            }
+            _ = tokio::time::sleep(POLL_INTERVAL), if watched_file.is_some() => {
+                let Some(file) = &watched_file else {
+                    continue;
+                };
+                let modified = modified_time(file).await;
+                if modified.is_some() && modified != last_modified {
+                    tokio::time::sleep(SETTLE_DELAY).await;
+                    last_modified = modified_time(file).await;
+                    info!("{} changed, reloading the configuration", file.display());
+                    reload(path.as_deref(), &config_tx).await;
+                }
+            }
+            result = close_rx.changed() => {
+                debug!("Closing configuration watcher");
+                if result.is_ok() && *close_rx.borrow_and_update() {
+                    break Ok(());
+                }
+            }
+        }
+    }
+}
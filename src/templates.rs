@@ -0,0 +1,126 @@
+use anyhow::{Result, anyhow};
+use minijinja::Environment;
+use serde::Serialize;
+
+use crate::{
+    config::{AppConfig, MessageTemplate},
+    notify::Destination,
+};
+
+/// Names of the notification types that can be customized
+pub const NOTIFICATION_TYPES: [&str; 7] = [
+    "media_available",
+    "ongoing_season_available",
+    "ongoing_episode_available",
+    "episode_digest_available",
+    "album_available",
+    "book_available",
+    "request_status_changed",
+];
+
+/// Parts of a message, the header is the Discord embed author and the Telegram first line, the
+/// body is the Discord embed description and the Telegram message text
+#[derive(Debug, Clone, Copy)]
+pub enum TemplatePart {
+    Header,
+    Title,
+    Body,
+}
+
+impl TemplatePart {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Header => "header",
+            Self::Title => "title",
+            Self::Body => "body",
+        }
+    }
+}
+
+/// User defined message templates compiled when the configuration is loaded
+///
+/// The templates use the MiniJinja syntax, the context contains the `type` of notification, the
+/// `media`, `requested_by`, `seasons`, `season_number`, `episode_number`, `episodes`, `movie`,
+/// `album`, `book`, `episode`, `request_status` and `play_url` values of the notification, the
+/// raw webhook `payload` and the `default` header, title and body that would be sent without
+/// template.
+#[derive(Clone)]
+pub struct MessageTemplates {
+    env: Environment<'static>,
+}
+
+impl MessageTemplates {
+    pub fn new(config: &AppConfig) -> Result<Self> {
+        let mut env = Environment::new();
+
+        let destinations = [
+            (
+                Destination::Discord,
+                config.discord.as_ref().map(|c| &c.templates),
+            ),
+            (
+                Destination::Telegram,
+                config.telegram.as_ref().map(|c| &c.templates),
+            ),
+        ];
+        for (destination, templates) in destinations {
+            for (kind, template) in templates.into_iter().flatten() {
+                add_templates(&mut env, destination, kind, template)?;
+            }
+        }
+
+        Ok(Self { env })
+    }
+
+    /// Renders the part of the message if there is a template for it
+    pub fn render(
+        &self,
+        destination: Destination,
+        kind: &str,
+        part: TemplatePart,
+        context: &impl Serialize,
+    ) -> Option<Result<String>> {
+        let name = template_name(destination, kind, part.name());
+        let template = self.env.get_template(&name).ok()?;
+        Some(
+            template
+                .render(context)
+                .map(|text| text.trim().to_string())
+                .map_err(|e| anyhow!("Could not render template {name}: {e:#}")),
+        )
+    }
+}
+
+fn template_name(destination: Destination, kind: &str, part: &str) -> String {
+    format!("{destination:?}.{kind}.{part}").to_lowercase()
+}
+
+fn add_templates(
+    env: &mut Environment<'static>,
+    destination: Destination,
+    kind: &str,
+    template: &MessageTemplate,
+) -> Result<()> {
+    if !NOTIFICATION_TYPES.contains(&kind) {
+        return Err(anyhow!(
+            "Unknown notification type '{kind}' in {destination:?} templates, expected one of: {}",
+            NOTIFICATION_TYPES.join(", ")
+        ));
+    }
+
+    let parts = [
+        (TemplatePart::Header, &template.header),
+        (TemplatePart::Title, &template.title),
+        (TemplatePart::Body, &template.body),
+    ];
+    for (part, source) in parts {
+        let Some(source) = source else {
+            continue;
+        };
+        let name = template_name(destination, kind, part.name());
+        env.add_template_owned(name.clone(), source.clone())
+            .map_err(|e| anyhow!("Invalid template {name}: {e:#}"))?;
+    }
+
+    Ok(())
+}
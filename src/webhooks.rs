@@ -1,3 +1,146 @@
-pub mod seerr;
+use std::sync::{
+    Mutex,
+    atomic::{AtomicU64, Ordering},
+};
+
+use axum::{Json, http::StatusCode};
+use log::warn;
+use tokio::sync::mpsc;
+
+use crate::MessageResponse;
+
+pub mod lidarr;
+pub mod mediaserver;
 pub mod radarr;
+pub mod readarr;
+pub mod seerr;
 pub mod sonarr;
+
+/// Event received by a webhook, tagged with the instance name given in the webhook path
+#[derive(Clone, Debug)]
+pub struct InstanceEvent<E> {
+    pub instance: Option<String>,
+    pub event: E,
+}
+
+/// The subscribers of the webhook cannot take more events
+#[derive(Debug, thiserror::Error)]
+#[error("The event queue is full")]
+pub struct EventQueueFull;
+
+/// Depth of the event queue of a webhook, exposed in the metrics
+#[derive(Debug, Clone)]
+pub struct EventQueueStats {
+    /// Events waiting to be processed by the slowest subscriber
+    pub depth: usize,
+    pub capacity: usize,
+    /// Events answered with a 503 because the queue was full
+    pub rejected: u64,
+}
+
+/// Registry of the subscribers of a webhook, every event is delivered to all of them in the
+/// order it was received
+///
+/// The lock is only held while the events are queued, so it is safe to emit from the async
+/// handlers.
+pub struct EventBus<E> {
+    subscribers: Mutex<Vec<mpsc::Sender<E>>>,
+    rejected: AtomicU64,
+}
+
+impl<E> Default for EventBus<E> {
+    fn default() -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+            rejected: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<E: Clone> EventBus<E> {
+    /// Receiver of the events, at most `capacity` of them wait to be received
+    pub fn subscribe(&self, capacity: usize) -> mpsc::Receiver<E> {
+        let (tx, rx) = mpsc::channel(capacity);
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Queues the event for every subscriber, the ones whose receiver was dropped are removed
+    ///
+    /// The event is not queued for any of them if one is full.
+    pub fn publish(&self, event: E) -> Result<(), EventQueueFull> {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|tx| !tx.is_closed());
+
+        let mut permits = Vec::with_capacity(subscribers.len());
+        for tx in subscribers.iter() {
+            match tx.try_reserve() {
+                Ok(permit) => permits.push(permit),
+                Err(_) => {
+                    self.rejected.fetch_add(1, Ordering::Relaxed);
+                    return Err(EventQueueFull);
+                }
+            }
+        }
+        for permit in permits {
+            permit.send(event.clone());
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> EventQueueStats {
+        let subscribers = self.subscribers.lock().unwrap();
+        EventQueueStats {
+            depth: subscribers
+                .iter()
+                .map(|tx| tx.max_capacity() - tx.capacity())
+                .max()
+                .unwrap_or(0),
+            capacity: subscribers
+                .iter()
+                .map(|tx| tx.max_capacity())
+                .min()
+                .unwrap_or(0),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Answer of the webhook handlers, the rejected events are answered with a 503 so Sonarr and
+/// Radarr send them again later
+pub fn emit_response(result: Result<(), EventQueueFull>) -> (StatusCode, Json<MessageResponse>) {
+    match result {
+        Ok(()) => (StatusCode::OK, Json(MessageResponse::ok())),
+        Err(e) => {
+            warn!("Rejecting webhook event: {e}");
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(MessageResponse::new(e.to_string())),
+            )
+        }
+    }
+}
+
+/// Webhook whose events are published to its [`EventBus`]
+pub trait WebhookEmitter {
+    type Event: Clone;
+
+    fn event_bus(&self) -> &EventBus<Self::Event>;
+
+    fn emit(&self, event: Self::Event) -> Result<(), EventQueueFull> {
+        self.event_bus().publish(event)
+    }
+}
+
+/// Webhook whose events can be received by other tasks
+pub trait WebhookListener: WebhookEmitter {
+    /// Receiver of the events emitted from now on, the webhook answers 503 while `capacity`
+    /// events are waiting
+    fn subscribe(&self, capacity: usize) -> mpsc::Receiver<Self::Event> {
+        self.event_bus().subscribe(capacity)
+    }
+
+    fn queue_stats(&self) -> EventQueueStats {
+        self.event_bus().stats()
+    }
+}
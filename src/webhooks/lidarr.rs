@@ -0,0 +1,243 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use log::{error, trace};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    MessageResponse,
+    webhooks::{EventBus, WebhookEmitter, WebhookListener, emit_response},
+};
+
+pub const TAG: &str = "lidarr";
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Image {
+    pub cover_type: String,
+    pub remote_url: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Artist {
+    pub id: i32,
+    pub name: String,
+    pub disambiguation: Option<String>,
+    pub path: String,
+    pub mb_id: Option<String>,
+    pub r#type: Option<String>,
+    pub overview: Option<String>,
+    pub genres: Option<Vec<String>>,
+    pub images: Option<Vec<Image>>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Album {
+    pub id: i32,
+    pub mb_id: Option<String>,
+    pub title: String,
+    pub disambiguation: Option<String>,
+    pub overview: Option<String>,
+    pub album_type: Option<String>,
+    pub release_date: Option<String>,
+    pub genres: Option<Vec<String>>,
+    pub images: Option<Vec<Image>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Track {
+    pub id: i32,
+    pub title: String,
+    pub track_number: String,
+    pub quality: Option<String>,
+    pub quality_version: Option<i32>,
+    pub release_group: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackFile {
+    pub id: i32,
+    pub path: String,
+    pub quality: Option<String>,
+    pub quality_version: Option<i32>,
+    pub release_group: Option<String>,
+    pub scene_name: Option<String>,
+    pub size: Option<i64>,
+    pub date_added: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Release {
+    pub custom_format_score: Option<i32>,
+    pub custom_formats: Option<Vec<String>>,
+    pub indexer: Option<String>,
+    pub quality: Option<String>,
+    pub quality_version: Option<i32>,
+    pub release_group: Option<String>,
+    pub release_title: Option<String>,
+    pub size: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GrabEvent {
+    pub application_url: String,
+    pub instance_name: String,
+    pub artist: Artist,
+    pub albums: Vec<Album>,
+    pub release: Release,
+    pub download_client: Option<String>,
+    pub download_client_type: Option<String>,
+    pub download_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AlbumDownloadEvent {
+    pub application_url: String,
+    pub instance_name: String,
+    pub artist: Artist,
+    pub album: Album,
+    pub tracks: Option<Vec<Track>>,
+    pub track_files: Vec<TrackFile>,
+    pub deleted_files: Option<Vec<TrackFile>>,
+    #[serde(default)]
+    pub is_upgrade: bool,
+    pub download_client: Option<String>,
+    pub download_client_type: Option<String>,
+    pub download_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackRetagEvent {
+    pub application_url: String,
+    pub instance_name: String,
+    pub artist: Artist,
+    pub track_file: TrackFile,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthEvent {
+    pub application_url: Option<String>,
+    pub instance_name: String,
+    pub level: String,
+    pub message: String,
+    pub r#type: String,
+    pub wiki_url: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthRestoredEvent {
+    pub instance_name: String,
+    pub level: String,
+    pub message: String,
+    pub r#type: String,
+    pub wiki_url: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TestEvent {
+    pub application_url: String,
+    pub instance_name: String,
+    pub artist: Artist,
+    pub albums: Vec<Album>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "eventType")]
+pub enum LidarrEvent {
+    Grab(GrabEvent),
+    #[serde(rename = "Download", alias = "AlbumDownload")]
+    AlbumDownload(AlbumDownloadEvent),
+    #[serde(rename = "Retag", alias = "TrackRetag")]
+    TrackRetag(TrackRetagEvent),
+    Health(HealthEvent),
+    HealthRestored(HealthRestoredEvent),
+    Test(TestEvent),
+}
+
+impl std::fmt::Display for LidarrEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let event_name = match self {
+            Self::Grab(_) => "Grab",
+            Self::AlbumDownload(_) => "AlbumDownload",
+            Self::TrackRetag(_) => "TrackRetag",
+            Self::Health(_) => "Health",
+            Self::HealthRestored(_) => "HealthRestored",
+            Self::Test(_) => "Test",
+        };
+        write!(f, "{}", event_name)
+    }
+}
+
+pub struct LidarrWebhook {
+    events: EventBus<LidarrEvent>,
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "lidarr_webhook",
+    path = "/v2/webhook",
+    request_body(content = LidarrEvent, content_type = "application/json"),
+    responses(
+        (status = StatusCode::OK, description = "Webhook received", body = MessageResponse),
+        (status = StatusCode::BAD_REQUEST, description = "Bad request", body = MessageResponse),
+        (status = StatusCode::SERVICE_UNAVAILABLE, description = "Event queue full, the webhook should be sent again later", body = MessageResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = MessageResponse)
+    ),
+    tag  = TAG
+)]
+async fn get_webhook(
+    State(webhook): State<Arc<LidarrWebhook>>,
+    json_str: String,
+) -> impl IntoResponse {
+    trace!("Event JSON: {}", json_str);
+    let data = match serde_json::from_str::<LidarrEvent>(&json_str) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("{}", e.to_string());
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(MessageResponse::new(e.to_string())),
+            );
+        }
+    };
+    emit_response(webhook.emit(data))
+}
+
+impl LidarrWebhook {
+    pub fn new() -> Arc<Self> {
+        Arc::new(LidarrWebhook {
+            events: EventBus::default(),
+        })
+    }
+
+    pub fn router(self: Arc<Self>) -> OpenApiRouter {
+        OpenApiRouter::new()
+            .routes(routes!(get_webhook))
+            .with_state(self)
+    }
+}
+
+impl WebhookEmitter for LidarrWebhook {
+    type Event = LidarrEvent;
+
+    fn event_bus(&self) -> &EventBus<LidarrEvent> {
+        &self.events
+    }
+}
+
+impl WebhookListener for LidarrWebhook {}
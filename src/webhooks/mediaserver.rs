@@ -0,0 +1,318 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Multipart, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use log::{error, trace};
+use serde::{Deserialize, Serialize};
+use serde_aux::prelude::*;
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    MessageResponse,
+    config::MediaServerKind,
+    webhooks::{EventBus, WebhookEmitter, WebhookListener, emit_response},
+};
+
+pub const TAG: &str = "mediaserver";
+
+/// Payload sent by the Jellyfin webhook plugin using the default generic template
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct JellyfinEvent {
+    #[serde(rename = "NotificationType")]
+    pub notification_type: String,
+    #[serde(rename = "ItemId")]
+    pub item_id: String,
+    #[serde(rename = "ItemType")]
+    pub item_type: String,
+    #[serde(rename = "Name")]
+    pub name: Option<String>,
+    #[serde(
+        rename = "Provider_tmdb",
+        default,
+        deserialize_with = "deserialize_option_number_from_string"
+    )]
+    pub provider_tmdb: Option<i32>,
+    #[serde(
+        rename = "Provider_tvdb",
+        default,
+        deserialize_with = "deserialize_option_number_from_string"
+    )]
+    pub provider_tvdb: Option<i32>,
+    #[serde(
+        rename = "SeasonNumber",
+        default,
+        deserialize_with = "deserialize_option_number_from_string"
+    )]
+    pub season_number: Option<i32>,
+    #[serde(
+        rename = "EpisodeNumber",
+        default,
+        deserialize_with = "deserialize_option_number_from_string"
+    )]
+    pub episode_number: Option<i32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct EmbyItem {
+    pub id: String,
+    pub name: Option<String>,
+    pub r#type: String,
+    #[serde(default)]
+    pub provider_ids: HashMap<String, String>,
+    pub parent_index_number: Option<i32>,
+    pub index_number: Option<i32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct EmbyEvent {
+    pub event: String,
+    pub item: Option<EmbyItem>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PlexGuid {
+    pub id: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlexMetadata {
+    pub rating_key: String,
+    pub r#type: String,
+    pub title: Option<String>,
+    #[serde(rename = "Guid", default)]
+    pub guids: Vec<PlexGuid>,
+    pub parent_index: Option<i32>,
+    pub index: Option<i32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PlexEvent {
+    pub event: String,
+    #[serde(rename = "Metadata")]
+    pub metadata: Option<PlexMetadata>,
+}
+
+/// Item added to the library of a media server
+#[derive(Clone, Debug, Serialize)]
+pub struct MediaServerEvent {
+    pub kind: MediaServerKind,
+    pub item_id: String,
+    pub item_type: String,
+    pub tmdb_id: Option<i32>,
+    pub tvdb_id: Option<i32>,
+    pub season_number: Option<i32>,
+    pub episode_number: Option<i32>,
+}
+
+impl std::fmt::Display for MediaServerEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let server_name = match self.kind {
+            MediaServerKind::Jellyfin => "Jellyfin",
+            MediaServerKind::Emby => "Emby",
+            MediaServerKind::Plex => "Plex",
+        };
+        write!(f, "{} {} added", server_name, self.item_type)
+    }
+}
+
+impl JellyfinEvent {
+    fn into_event(self) -> Option<MediaServerEvent> {
+        if self.notification_type != "ItemAdded" {
+            return None;
+        }
+        Some(MediaServerEvent {
+            kind: MediaServerKind::Jellyfin,
+            item_id: self.item_id,
+            item_type: self.item_type,
+            tmdb_id: self.provider_tmdb,
+            tvdb_id: self.provider_tvdb,
+            season_number: self.season_number,
+            episode_number: self.episode_number,
+        })
+    }
+}
+
+impl EmbyEvent {
+    fn into_event(self) -> Option<MediaServerEvent> {
+        if self.event != "library.new" {
+            return None;
+        }
+        let item = self.item?;
+        let provider_id = |name: &str| {
+            item.provider_ids
+                .iter()
+                .find(|(provider, _)| provider.eq_ignore_ascii_case(name))
+                .and_then(|(_, id)| id.parse::<i32>().ok())
+        };
+        Some(MediaServerEvent {
+            kind: MediaServerKind::Emby,
+            tmdb_id: provider_id("tmdb"),
+            tvdb_id: provider_id("tvdb"),
+            item_id: item.id,
+            item_type: item.r#type,
+            season_number: item.parent_index_number,
+            episode_number: item.index_number,
+        })
+    }
+}
+
+impl PlexEvent {
+    fn into_event(self) -> Option<MediaServerEvent> {
+        if self.event != "library.new" {
+            return None;
+        }
+        let metadata = self.metadata?;
+        let provider_id = |scheme: &str| {
+            metadata
+                .guids
+                .iter()
+                .find_map(|guid| guid.id.strip_prefix(scheme))
+                .and_then(|id| id.parse::<i32>().ok())
+        };
+        Some(MediaServerEvent {
+            kind: MediaServerKind::Plex,
+            tmdb_id: provider_id("tmdb://"),
+            tvdb_id: provider_id("tvdb://"),
+            item_id: metadata.rating_key,
+            item_type: metadata.r#type,
+            season_number: metadata.parent_index,
+            episode_number: metadata.index,
+        })
+    }
+}
+
+pub struct MediaServerWebhook {
+    events: EventBus<MediaServerEvent>,
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "jellyfin_webhook",
+    path = "/jellyfin/webhook",
+    request_body(content = JellyfinEvent, content_type = "application/json"),
+    responses(
+        (status = StatusCode::OK, description = "Webhook received", body = MessageResponse),
+        (status = StatusCode::BAD_REQUEST, description = "Bad request", body = MessageResponse),
+        (status = StatusCode::SERVICE_UNAVAILABLE, description = "Event queue full, the webhook should be sent again later", body = MessageResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = MessageResponse)
+    ),
+    tag  = TAG
+)]
+async fn get_jellyfin_webhook(
+    State(webhook): State<Arc<MediaServerWebhook>>,
+    json_str: String,
+) -> impl IntoResponse {
+    trace!("Event JSON: {}", json_str);
+    match serde_json::from_str::<JellyfinEvent>(&json_str) {
+        Ok(data) => webhook.emit_added(data.into_event()),
+        Err(e) => bad_request(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "emby_webhook",
+    path = "/emby/webhook",
+    request_body(content = EmbyEvent, content_type = "application/json"),
+    responses(
+        (status = StatusCode::OK, description = "Webhook received", body = MessageResponse),
+        (status = StatusCode::BAD_REQUEST, description = "Bad request", body = MessageResponse),
+        (status = StatusCode::SERVICE_UNAVAILABLE, description = "Event queue full, the webhook should be sent again later", body = MessageResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = MessageResponse)
+    ),
+    tag  = TAG
+)]
+async fn get_emby_webhook(
+    State(webhook): State<Arc<MediaServerWebhook>>,
+    json_str: String,
+) -> impl IntoResponse {
+    trace!("Event JSON: {}", json_str);
+    match serde_json::from_str::<EmbyEvent>(&json_str) {
+        Ok(data) => webhook.emit_added(data.into_event()),
+        Err(e) => bad_request(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "plex_webhook",
+    path = "/plex/webhook",
+    request_body(content = PlexEvent, content_type = "multipart/form-data"),
+    responses(
+        (status = StatusCode::OK, description = "Webhook received", body = MessageResponse),
+        (status = StatusCode::BAD_REQUEST, description = "Bad request", body = MessageResponse),
+        (status = StatusCode::SERVICE_UNAVAILABLE, description = "Event queue full, the webhook should be sent again later", body = MessageResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = MessageResponse)
+    ),
+    tag  = TAG
+)]
+async fn get_plex_webhook(
+    State(webhook): State<Arc<MediaServerWebhook>>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    // Plex sends the JSON in the `payload` field alongside an optional thumbnail
+    let json_str = loop {
+        match multipart.next_field().await {
+            Ok(Some(field)) if field.name() == Some("payload") => match field.text().await {
+                Ok(text) => break text,
+                Err(e) => return bad_request(e.to_string()),
+            },
+            Ok(Some(_)) => continue,
+            Ok(None) => return bad_request("Missing payload field".to_string()),
+            Err(e) => return bad_request(e.to_string()),
+        }
+    };
+
+    trace!("Event JSON: {}", json_str);
+    match serde_json::from_str::<PlexEvent>(&json_str) {
+        Ok(data) => webhook.emit_added(data.into_event()),
+        Err(e) => bad_request(e.to_string()),
+    }
+}
+
+fn bad_request(message: String) -> (StatusCode, Json<MessageResponse>) {
+    error!("{}", message);
+    (StatusCode::BAD_REQUEST, Json(MessageResponse::new(message)))
+}
+
+impl MediaServerWebhook {
+    pub fn new() -> Arc<Self> {
+        Arc::new(MediaServerWebhook {
+            events: EventBus::default(),
+        })
+    }
+
+    pub fn router(self: Arc<Self>) -> OpenApiRouter {
+        OpenApiRouter::new()
+            .routes(routes!(get_jellyfin_webhook))
+            .routes(routes!(get_emby_webhook))
+            .routes(routes!(get_plex_webhook))
+            .with_state(self)
+    }
+
+    fn emit_added(&self, event: Option<MediaServerEvent>) -> (StatusCode, Json<MessageResponse>) {
+        // Only the events of new library items are relevant
+        match event {
+            Some(event) => emit_response(self.emit(event)),
+            None => (StatusCode::OK, Json(MessageResponse::ok())),
+        }
+    }
+}
+
+impl WebhookEmitter for MediaServerWebhook {
+    type Event = MediaServerEvent;
+
+    fn event_bus(&self) -> &EventBus<MediaServerEvent> {
+        &self.events
+    }
+}
+
+impl WebhookListener for MediaServerWebhook {}
@@ -3,13 +3,12 @@ use std::sync::Arc;
 use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
 use log::{error, trace};
 use serde::{Deserialize, Serialize};
-use tokio::sync::{Mutex, mpsc};
 use utoipa::ToSchema;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 use crate::{
     MessageResponse,
-    webhooks::{WebhookEmitter, WebhookListener},
+    webhooks::{EventBus, WebhookEmitter, WebhookListener, emit_response},
 };
 
 pub const TAG: &str = "radarr";
@@ -304,7 +303,7 @@ impl std::fmt::Display for RadarrEvent {
 }
 
 pub struct RadarrWebhook {
-    listeners: Mutex<Vec<mpsc::UnboundedSender<RadarrEvent>>>,
+    events: EventBus<RadarrEvent>,
 }
 
 #[utoipa::path(
@@ -315,6 +314,7 @@ pub struct RadarrWebhook {
     responses(
         (status = StatusCode::OK, description = "Webhook received", body = MessageResponse),
         (status = StatusCode::BAD_REQUEST, description = "Bad request", body = MessageResponse),
+        (status = StatusCode::SERVICE_UNAVAILABLE, description = "Event queue full, the webhook should be sent again later", body = MessageResponse),
         (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = MessageResponse)
     ),
     tag  = TAG
@@ -334,13 +334,14 @@ async fn get_webhook(
             );
         }
     };
-    webhook.emit(&data);
-    (StatusCode::OK, Json(MessageResponse::ok()))
+    emit_response(webhook.emit(data))
 }
 
 impl RadarrWebhook {
     pub fn new() -> Arc<Self> {
-        Arc::new(RadarrWebhook { listeners: Mutex::new(vec![]) })
+        Arc::new(RadarrWebhook {
+            events: EventBus::default(),
+        })
     }
 
     pub fn router(self: Arc<Self>) -> OpenApiRouter {
@@ -350,26 +351,12 @@ impl RadarrWebhook {
     }
 }
 
-impl WebhookListener for RadarrWebhook {
-    type Event = RadarrEvent;
-
-    fn add_listener(&self, listener: impl Fn(RadarrEvent) + Send + 'static) {
-        let (tx, mut rx) = mpsc::unbounded_channel();
-        self.listeners.blocking_lock().push(tx);
-        tokio::spawn(async move {
-            while let Some(event) = rx.recv().await {
-                listener(event);
-            }
-        });
-    }
-}
-
 impl WebhookEmitter for RadarrWebhook {
     type Event = RadarrEvent;
 
-    fn emit(&self, event: &RadarrEvent) {
-        for tx in self.listeners.blocking_lock().iter() {
-            let _ = tx.send(event.clone());
-        }
+    fn event_bus(&self) -> &EventBus<RadarrEvent> {
+        &self.events
     }
 }
+
+impl WebhookListener for RadarrWebhook {}
@@ -0,0 +1,238 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use log::{error, trace};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    MessageResponse,
+    webhooks::{EventBus, WebhookEmitter, WebhookListener, emit_response},
+};
+
+pub const TAG: &str = "readarr";
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Image {
+    pub cover_type: String,
+    pub remote_url: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Author {
+    pub id: i32,
+    pub name: String,
+    pub path: String,
+    pub goodreads_id: Option<String>,
+    pub images: Option<Vec<Image>>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Book {
+    pub id: i32,
+    pub goodreads_id: Option<String>,
+    pub title: String,
+    pub overview: Option<String>,
+    pub release_date: Option<String>,
+    pub images: Option<Vec<Image>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BookFile {
+    pub id: i32,
+    pub path: String,
+    pub quality: Option<String>,
+    pub quality_version: Option<i32>,
+    pub release_group: Option<String>,
+    pub scene_name: Option<String>,
+    pub size: Option<i64>,
+    pub date_added: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Release {
+    pub custom_format_score: Option<i32>,
+    pub custom_formats: Option<Vec<String>>,
+    pub indexer: Option<String>,
+    pub quality: Option<String>,
+    pub quality_version: Option<i32>,
+    pub release_group: Option<String>,
+    pub release_title: Option<String>,
+    pub size: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GrabEvent {
+    pub application_url: String,
+    pub instance_name: String,
+    pub author: Author,
+    pub books: Vec<Book>,
+    pub release: Release,
+    pub download_client: Option<String>,
+    pub download_client_type: Option<String>,
+    pub download_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadEvent {
+    pub application_url: String,
+    pub instance_name: String,
+    pub author: Author,
+    pub book: Book,
+    pub book_files: Vec<BookFile>,
+    pub deleted_files: Option<Vec<BookFile>>,
+    #[serde(default)]
+    pub is_upgrade: bool,
+    pub download_client: Option<String>,
+    pub download_client_type: Option<String>,
+    pub download_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BookDeleteEvent {
+    pub application_url: String,
+    pub instance_name: String,
+    pub author: Author,
+    pub book: Book,
+    #[serde(default)]
+    pub deleted_files: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BookFileDeleteEvent {
+    pub application_url: String,
+    pub instance_name: String,
+    pub author: Author,
+    pub book: Book,
+    pub book_file: BookFile,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthEvent {
+    pub application_url: Option<String>,
+    pub instance_name: String,
+    pub level: String,
+    pub message: String,
+    pub r#type: String,
+    pub wiki_url: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthRestoredEvent {
+    pub instance_name: String,
+    pub level: String,
+    pub message: String,
+    pub r#type: String,
+    pub wiki_url: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TestEvent {
+    pub application_url: String,
+    pub instance_name: String,
+    pub author: Author,
+    pub books: Vec<Book>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "eventType")]
+pub enum ReadarrEvent {
+    Grab(GrabEvent),
+    #[serde(alias = "BookFileImport")]
+    Download(DownloadEvent),
+    #[serde(alias = "Delete")]
+    BookDelete(BookDeleteEvent),
+    BookFileDelete(BookFileDeleteEvent),
+    Health(HealthEvent),
+    HealthRestored(HealthRestoredEvent),
+    Test(TestEvent),
+}
+
+impl std::fmt::Display for ReadarrEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let event_name = match self {
+            Self::Grab(_) => "Grab",
+            Self::Download(_) => "Download",
+            Self::BookDelete(_) => "BookDelete",
+            Self::BookFileDelete(_) => "BookFileDelete",
+            Self::Health(_) => "Health",
+            Self::HealthRestored(_) => "HealthRestored",
+            Self::Test(_) => "Test",
+        };
+        write!(f, "{}", event_name)
+    }
+}
+
+pub struct ReadarrWebhook {
+    events: EventBus<ReadarrEvent>,
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "readarr_webhook",
+    path = "/v1/webhook",
+    request_body(content = ReadarrEvent, content_type = "application/json"),
+    responses(
+        (status = StatusCode::OK, description = "Webhook received", body = MessageResponse),
+        (status = StatusCode::BAD_REQUEST, description = "Bad request", body = MessageResponse),
+        (status = StatusCode::SERVICE_UNAVAILABLE, description = "Event queue full, the webhook should be sent again later", body = MessageResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = MessageResponse)
+    ),
+    tag  = TAG
+)]
+async fn get_webhook(
+    State(webhook): State<Arc<ReadarrWebhook>>,
+    json_str: String,
+) -> impl IntoResponse {
+    trace!("Event JSON: {}", json_str);
+    let data = match serde_json::from_str::<ReadarrEvent>(&json_str) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("{}", e.to_string());
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(MessageResponse::new(e.to_string())),
+            );
+        }
+    };
+    emit_response(webhook.emit(data))
+}
+
+impl ReadarrWebhook {
+    pub fn new() -> Arc<Self> {
+        Arc::new(ReadarrWebhook {
+            events: EventBus::default(),
+        })
+    }
+
+    pub fn router(self: Arc<Self>) -> OpenApiRouter {
+        OpenApiRouter::new()
+            .routes(routes!(get_webhook))
+            .with_state(self)
+    }
+}
+
+impl WebhookEmitter for ReadarrWebhook {
+    type Event = ReadarrEvent;
+
+    fn event_bus(&self) -> &EventBus<ReadarrEvent> {
+        &self.events
+    }
+}
+
+impl WebhookListener for ReadarrWebhook {}
@@ -5,13 +5,12 @@ use log::{error, trace};
 use serde::{Deserialize, Serialize};
 use serde_aux::prelude::*;
 use serde_json::Value;
-use tokio::sync::{Mutex, mpsc};
 use utoipa::ToSchema;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 use crate::{
     MessageResponse,
-    webhooks::{WebhookEmitter, WebhookListener},
+    webhooks::{EventBus, WebhookEmitter, WebhookListener, emit_response},
 };
 
 pub const TAG: &str = "seerr";
@@ -188,7 +187,7 @@ pub struct SeerrEvent {
 }
 
 pub struct SeerrWebhook {
-    listeners: Mutex<Vec<mpsc::UnboundedSender<SeerrEvent>>>,
+    events: EventBus<SeerrEvent>,
 }
 
 #[utoipa::path(
@@ -199,6 +198,7 @@ pub struct SeerrWebhook {
     responses(
         (status = StatusCode::OK, description = "Webhook received", body = MessageResponse),
         (status = StatusCode::BAD_REQUEST, description = "Bad request", body = MessageResponse),
+        (status = StatusCode::SERVICE_UNAVAILABLE, description = "Event queue full, the webhook should be sent again later", body = MessageResponse),
         (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = MessageResponse)
     ),
     tag  = TAG
@@ -218,13 +218,14 @@ async fn get_webhook(
             );
         }
     };
-    webhook.emit(&data);
-    (StatusCode::OK, Json(MessageResponse::ok()))
+    emit_response(webhook.emit(data))
 }
 
 impl SeerrWebhook {
     pub fn new() -> Arc<Self> {
-        Arc::new(SeerrWebhook { listeners: Mutex::new(vec![]) })
+        Arc::new(SeerrWebhook {
+            events: EventBus::default(),
+        })
     }
 
     pub fn router(self: Arc<Self>) -> OpenApiRouter {
@@ -234,26 +235,12 @@ impl SeerrWebhook {
     }
 }
 
-impl WebhookListener for SeerrWebhook {
-    type Event = SeerrEvent;
-
-    fn add_listener(&self, listener: impl Fn(SeerrEvent) + Send + 'static) {
-        let (tx, mut rx) = mpsc::unbounded_channel();
-        self.listeners.blocking_lock().push(tx);
-        tokio::spawn(async move {
-            while let Some(event) = rx.recv().await {
-                listener(event);
-            }
-        });
-    }
-}
-
 impl WebhookEmitter for SeerrWebhook {
     type Event = SeerrEvent;
 
-    fn emit(&self, event: &SeerrEvent) {
-        for tx in self.listeners.blocking_lock().iter() {
-            let _ = tx.send(event.clone());
-        }
+    fn event_bus(&self) -> &EventBus<SeerrEvent> {
+        &self.events
     }
 }
+
+impl WebhookListener for SeerrWebhook {}
@@ -1,15 +1,19 @@
 use std::sync::Arc;
 
-use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
 use log::{error, trace};
 use serde::{Deserialize, Serialize};
-use tokio::sync::{Mutex, mpsc};
 use utoipa::ToSchema;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 use crate::{
     MessageResponse,
-    webhooks::{WebhookEmitter, WebhookListener},
+    webhooks::{EventBus, InstanceEvent, WebhookEmitter, WebhookListener, emit_response},
 };
 
 pub const TAG: &str = "sonarr";
@@ -310,7 +314,7 @@ impl std::fmt::Display for SonarrEvent {
 }
 
 pub struct SonarrWebhook {
-    listeners: Mutex<Vec<mpsc::UnboundedSender<SonarrEvent>>>,
+    events: EventBus<InstanceEvent<SonarrEvent>>,
 }
 
 #[utoipa::path(
@@ -321,6 +325,7 @@ pub struct SonarrWebhook {
     responses(
         (status = StatusCode::OK, description = "Webhook received", body = MessageResponse),
         (status = StatusCode::BAD_REQUEST, description = "Bad request", body = MessageResponse),
+        (status = StatusCode::SERVICE_UNAVAILABLE, description = "Event queue full, the webhook should be sent again later", body = MessageResponse),
         (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = MessageResponse)
     ),
     tag  = TAG
@@ -329,6 +334,38 @@ async fn get_webhook(
     State(webhook): State<Arc<SonarrWebhook>>,
     json_str: String,
 ) -> impl IntoResponse {
+    handle_webhook(&webhook, None, json_str)
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "sonarr_instance_webhook",
+    path = "/{instance}/v4/webhook",
+    params(
+        ("instance" = String, Path, description = "Name of the Sonarr instance sending the event")
+    ),
+    request_body(content = SonarrEvent, content_type = "application/json"),
+    responses(
+        (status = StatusCode::OK, description = "Webhook received", body = MessageResponse),
+        (status = StatusCode::BAD_REQUEST, description = "Bad request", body = MessageResponse),
+        (status = StatusCode::SERVICE_UNAVAILABLE, description = "Event queue full, the webhook should be sent again later", body = MessageResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = MessageResponse)
+    ),
+    tag  = TAG
+)]
+async fn get_instance_webhook(
+    State(webhook): State<Arc<SonarrWebhook>>,
+    Path(instance): Path<String>,
+    json_str: String,
+) -> impl IntoResponse {
+    handle_webhook(&webhook, Some(instance), json_str)
+}
+
+fn handle_webhook(
+    webhook: &SonarrWebhook,
+    instance: Option<String>,
+    json_str: String,
+) -> (StatusCode, Json<MessageResponse>) {
     trace!("Event JSON: {}", json_str);
     let data = match serde_json::from_str::<SonarrEvent>(&json_str) {
         Ok(data) => data,
@@ -340,54 +377,33 @@ async fn get_webhook(
             );
         }
     };
-    webhook.emit(&data);
-    // if let Err(e) = state.send(data) {
-    //     error!("{}", e.to_string());
-    //     if let Err(e) = closer.send(true) {
-    //         error!("Could not send close request: {e}");
-    //     }
-    //     return (
-    //         StatusCode::INTERNAL_SERVER_ERROR,
-    //         Json(MessageResponse::new(e.to_string())),
-    //     );
-    // };
-    (StatusCode::OK, Json(MessageResponse::ok()))
+    emit_response(webhook.emit(InstanceEvent {
+        instance,
+        event: data,
+    }))
 }
 
 impl SonarrWebhook {
     pub fn new() -> Arc<Self> {
         Arc::new(SonarrWebhook {
-            listeners: Mutex::new(vec![]),
+            events: EventBus::default(),
         })
     }
 
     pub fn router(self: Arc<Self>) -> OpenApiRouter {
         OpenApiRouter::new()
             .routes(routes!(get_webhook))
+            .routes(routes!(get_instance_webhook))
             .with_state(self)
     }
 }
 
-impl WebhookListener for SonarrWebhook {
-    type Event = SonarrEvent;
-
-    fn add_listener(&self, listener: impl Fn(SonarrEvent) + Send + 'static) {
-        let (tx, mut rx) = mpsc::unbounded_channel();
-        self.listeners.blocking_lock().push(tx);
-        tokio::spawn(async move {
-            while let Some(event) = rx.recv().await {
-                listener(event);
-            }
-        });
-    }
-}
-
 impl WebhookEmitter for SonarrWebhook {
-    type Event = SonarrEvent;
+    type Event = InstanceEvent<SonarrEvent>;
 
-    fn emit(&self, event: &SonarrEvent) {
-        for tx in self.listeners.blocking_lock().iter() {
-            let _ = tx.send(event.clone());
-        }
+    fn event_bus(&self) -> &EventBus<InstanceEvent<SonarrEvent>> {
+        &self.events
     }
 }
+
+impl WebhookListener for SonarrWebhook {}